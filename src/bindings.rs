@@ -0,0 +1,238 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::ptx::{Linkage, Module};
+
+/// Generates host-side Rust module for the PTX assembly.
+///
+/// The module contains the assembly itself and typed descriptors of its visible kernels
+/// (the only ones, that can be launched from the host):
+/// ```ignore
+/// pub const PTX: &str = include_str!("/path/to/assembly.ptx");
+///
+/// pub mod kernels {
+///     pub const THE_KERNEL: super::Kernel = super::Kernel {
+///         name: "the_kernel",
+///         param_count: 2,
+///         param_types: &[".u64", ".u64"],
+///     };
+/// }
+///
+/// pub const KERNELS: &[Kernel] = &[kernels::THE_KERNEL];
+/// ```
+///
+/// Kernels, whose names map to the same constant (e.g. `foo_bar` and `foo$bar`),
+/// get numeric suffixes in order of appearance: `FOO_BAR` and `FOO_BAR_1`.
+pub fn generate(module: &Module, assembly_path: &Path) -> String {
+    let mut output = String::new();
+    let mut kernel_names = Vec::new();
+    let mut const_names = HashSet::new();
+
+    output.push_str("// Generated by ptx-builder, do not edit.\n\n");
+
+    output.push_str("/// Kernel entry point of the PTX assembly.\n");
+    output.push_str("#[derive(Clone, Copy, Debug, PartialEq, Eq)]\n");
+    output.push_str("pub struct Kernel {\n");
+    output.push_str("    pub name: &'static str,\n");
+    output.push_str("    pub param_count: usize,\n");
+    output.push_str("    pub param_types: &'static [&'static str],\n");
+    output.push_str("}\n\n");
+
+    output.push_str("/// PTX assembly of the device crate.\n");
+    writeln!(
+        output,
+        "pub const PTX: &str = include_str!({:?});\n",
+        assembly_path.display().to_string()
+    )
+    .unwrap();
+
+    output.push_str("/// Descriptors of every visible `.entry` of the assembly.\n");
+    output.push_str("pub mod kernels {\n");
+
+    let visible_kernels = {
+        module
+            .kernels
+            .iter()
+            .filter(|kernel| kernel.linkage == Linkage::Visible)
+    };
+
+    for kernel in visible_kernels {
+        let const_name = get_unique_const_name(&kernel.name, &mut const_names);
+        let param_types: Vec<String> = {
+            kernel
+                .params
+                .iter()
+                .map(|param| format!("{:?}", param.ty))
+                .collect()
+        };
+
+        writeln!(
+            output,
+            "    pub const {}: super::Kernel = super::Kernel {{",
+            const_name
+        )
+        .unwrap();
+        writeln!(output, "        name: {:?},", kernel.name).unwrap();
+        writeln!(output, "        param_count: {},", kernel.params.len()).unwrap();
+        writeln!(
+            output,
+            "        param_types: &[{}],",
+            param_types.join(", ")
+        )
+        .unwrap();
+        writeln!(output, "    }};").unwrap();
+
+        kernel_names.push(format!("kernels::{}", const_name));
+    }
+
+    output.push_str("}\n\n");
+
+    output.push_str("/// All kernels of the assembly.\n");
+    writeln!(
+        output,
+        "pub const KERNELS: &[Kernel] = &[{}];",
+        kernel_names.join(", ")
+    )
+    .unwrap();
+
+    output
+}
+
+/// Converts PTX identifier into Rust constant name, that is not in `taken` yet.
+fn get_unique_const_name(name: &str, taken: &mut HashSet<String>) -> String {
+    let base_name = get_const_name(name);
+    let mut const_name = base_name.clone();
+    let mut suffix = 0;
+
+    while taken.contains(&const_name) {
+        suffix += 1;
+        const_name = format!("{}_{}", base_name, suffix);
+    }
+
+    taken.insert(const_name.clone());
+    const_name
+}
+
+/// Converts PTX identifier (that may contain `$` or `%`) into Rust constant name.
+fn get_const_name(name: &str) -> String {
+    let name: String = {
+        name.chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' => c.to_ascii_uppercase(),
+                _ => '_',
+            })
+            .collect()
+    };
+
+    match name.chars().next() {
+        Some('0'..='9') => format!("_{}", name),
+        _ => name,
+    }
+}
+
+#[test]
+fn should_generate_kernel_descriptors() {
+    let module = Module::parse(
+        r#"
+.version 6.0
+.target sm_61
+.address_size 64
+
+.visible .entry the_kernel(
+	.param .u64 the_kernel_param_0,
+	.param .f64 the_kernel_param_1
+)
+{
+	ret;
+}
+
+.visible .entry $other$kernel()
+{
+	ret;
+}
+
+.entry hidden_kernel()
+{
+	ret;
+}
+"#,
+    )
+    .unwrap();
+
+    let bindings = generate(&module, Path::new("/path/to/sample_ptx_crate.ptx"));
+
+    assert!(!bindings.contains("HIDDEN_KERNEL"));
+    assert!(bindings
+        .contains(r#"pub const PTX: &str = include_str!("/path/to/sample_ptx_crate.ptx");"#));
+
+    assert!(bindings.contains(
+        r#"    pub const THE_KERNEL: super::Kernel = super::Kernel {
+        name: "the_kernel",
+        param_count: 2,
+        param_types: &[".u64", ".f64"],
+    };"#
+    ));
+
+    assert!(bindings.contains(
+        r#"    pub const _OTHER_KERNEL: super::Kernel = super::Kernel {
+        name: "$other$kernel",
+        param_count: 0,
+        param_types: &[],
+    };"#
+    ));
+
+    assert!(bindings.contains(
+        "pub const KERNELS: &[Kernel] = &[kernels::THE_KERNEL, kernels::_OTHER_KERNEL];"
+    ));
+}
+
+#[test]
+fn should_convert_names_into_constants() {
+    assert_eq!(get_const_name("the_kernel"), "THE_KERNEL");
+    assert_eq!(get_const_name("kernel%1"), "KERNEL_1");
+    assert_eq!(get_const_name("2d_blur"), "_2D_BLUR");
+}
+
+#[test]
+fn should_resolve_constant_name_collisions() {
+    let module = Module::parse(
+        r#"
+.version 6.0
+.target sm_61
+.address_size 64
+
+.visible .entry foo_bar()
+{
+	ret;
+}
+
+.visible .entry foo$bar()
+{
+	ret;
+}
+
+.visible .entry foo_bar_1()
+{
+	ret;
+}
+"#,
+    )
+    .unwrap();
+
+    let bindings = generate(&module, Path::new("/path/to/sample_ptx_crate.ptx"));
+
+    assert!(bindings.contains(
+        "    pub const FOO_BAR: super::Kernel = super::Kernel {\n        name: \"foo_bar\","
+    ));
+    assert!(bindings.contains(
+        "    pub const FOO_BAR_1: super::Kernel = super::Kernel {\n        name: \"foo$bar\","
+    ));
+    assert!(bindings.contains(
+        "    pub const FOO_BAR_1_1: super::Kernel = super::Kernel {\n        name: \"foo_bar_1\","
+    ));
+
+    assert!(bindings.contains(
+        "pub const KERNELS: &[Kernel] = &[kernels::FOO_BAR, kernels::FOO_BAR_1, kernels::FOO_BAR_1_1];"
+    ));
+}
@@ -1,19 +1,24 @@
+use std::collections::BTreeMap;
 use std::env;
 use std::fmt;
-use std::fs::{read_to_string, write, File};
-use std::io::{BufReader, Read};
+use std::fs::{read, read_to_string, write};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use failure::ResultExt;
-use lazy_static::*;
-use regex::Regex;
+use serde::Serialize;
 
+use crate::bindings;
+use crate::cache::{Cache, CacheKey};
 use crate::error::*;
-use crate::executable::{Cargo, ExecutableRunner, Linker};
-use crate::source::Crate;
+use crate::executable::{CommitDate, ExecutableRunner, Linker, ToolchainCargo};
+use crate::lint::{self, Lint, DEFAULT_STACK_FRAME_LIMIT};
+use crate::messages::Messages;
+use crate::ptx::{Kernel, Linkage, Module};
+use crate::source::{Crate, Target};
 
-const LAST_BUILD_CMD: &str = ".last-build-command";
 const TARGET_NAME: &str = "nvptx64-nvidia-cuda";
+const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(600);
 
 /// Core of the crate - PTX assembly build controller.
 #[derive(Debug)]
@@ -21,16 +26,62 @@ pub struct Builder {
     source_crate: Crate,
 
     profile: Profile,
+    opt_level: Option<String>,
+    debug_info: Option<String>,
     colors: bool,
     crate_type: Option<CrateType>,
+    target_name: Option<String>,
+    target_cpu: Option<String>,
+    target_features: Vec<String>,
+    rustc_flags: Vec<String>,
+    toolchain: Option<String>,
+    required_nightly_date: Option<CommitDate>,
+    out_dir: Option<PathBuf>,
+    cache_root: Option<PathBuf>,
+    lock_timeout: Duration,
+    expected_kernels: Vec<String>,
+    stack_frame_limit: usize,
+
+    features: Vec<String>,
+    all_features: bool,
+    no_default_features: bool,
 }
 
 /// Successful build output.
 #[derive(Debug)]
 pub struct BuildOutput<'a> {
     builder: &'a Builder,
-    output_path: PathBuf,
-    file_suffix: String,
+    artifact_path: PathBuf,
+    assembly_path: PathBuf,
+    fresh: bool,
+    target_name: String,
+    target_cpu: Option<String>,
+}
+
+/// Machine-readable description of the assembly and its kernels.
+#[derive(Serialize)]
+struct KernelManifest<'a> {
+    #[serde(rename = "crate")]
+    crate_name: &'a str,
+    target: &'a str,
+    target_cpu: Option<&'a str>,
+    profile: String,
+    assembly: &'a Path,
+    ptx: PtxManifest<'a>,
+    kernels: &'a [Kernel],
+}
+
+#[derive(Serialize)]
+struct PtxManifest<'a> {
+    version: Option<&'a str>,
+    target: &'a [String],
+    address_size: Option<u32>,
+}
+
+/// Successful multi-architecture build output.
+#[derive(Debug)]
+pub struct MultiBuildOutput<'a> {
+    outputs: BTreeMap<String, BuildOutput<'a>>,
 }
 
 /// Non-failed build status.
@@ -45,7 +96,19 @@ pub enum BuildStatus<'a> {
     NotNeeded,
 }
 
-/// Debug / Release profile.
+/// Non-failed multi-architecture build status.
+///
+/// Behaves the same way as [`BuildStatus`](enum.BuildStatus.html).
+#[derive(Debug)]
+pub enum MultiBuildStatus<'a> {
+    /// The CUDA crate was built without errors for every architecture.
+    Success(MultiBuildOutput<'a>),
+
+    /// The CUDA crate building is not needed.
+    NotNeeded,
+}
+
+/// Debug / Release or custom profile.
 ///
 /// # Usage
 /// ``` no_run
@@ -66,6 +129,17 @@ pub enum Profile {
 
     /// Equivalent for `cargo-build` **with** `--release` flag.
     Release,
+
+    /// Equivalent for `cargo-build` **with** `--profile <name>` flag.
+    ///
+    /// The profile has to be declared in the device crate's `Cargo.toml`,
+    /// unless it's one of Cargo built-in profiles:
+    /// ```text
+    /// [profile.gpu-release]
+    /// inherits = "release"
+    /// opt-level = 2
+    /// ```
+    Custom(String),
 }
 
 /// Build specified crate type.
@@ -89,10 +163,15 @@ pub enum Profile {
 /// # Ok(())
 /// # }
 /// ```
-#[derive(Clone, Copy, Debug)]
+#[derive(PartialEq, Hash, Clone, Copy, Debug)]
 pub enum CrateType {
     Library,
     Binary,
+
+    /// Example target, has to be chosen by name with
+    /// [`Builder::set_target_name`](struct.Builder.html#method.set_target_name)
+    /// unless the crate has only one.
+    Example,
 }
 
 impl Builder {
@@ -120,9 +199,26 @@ impl Builder {
         Ok(Builder {
             source_crate: Crate::analyse(path).context("Unable to analyse source crate")?,
 
-            profile: Profile::Release, // TODO: choose automatically, e.g.: `env::var("PROFILE").unwrap_or("release".to_string())`
+            profile: Profile::Release,
+            opt_level: None,
+            debug_info: None,
             colors: true,
             crate_type: None,
+            target_name: None,
+            target_cpu: None,
+            target_features: vec![],
+            rustc_flags: vec![],
+            toolchain: None,
+            required_nightly_date: None,
+            out_dir: None,
+            cache_root: None,
+            lock_timeout: DEFAULT_LOCK_TIMEOUT,
+            expected_kernels: vec![],
+            stack_frame_limit: DEFAULT_STACK_FRAME_LIMIT,
+
+            features: vec![],
+            all_features: false,
+            no_default_features: false,
         })
     }
 
@@ -140,6 +236,11 @@ impl Builder {
         !is_rls_build && !is_recursive_build
     }
 
+    /// Returns root path of the device crate.
+    pub fn get_crate_path(&self) -> &Path {
+        self.source_crate.get_path()
+    }
+
     /// Disable colors for internal calls to `cargo`.
     pub fn disable_colors(mut self) -> Self {
         self.colors = false;
@@ -152,6 +253,39 @@ impl Builder {
         self
     }
 
+    /// Set build profile to match the one of host crate.
+    ///
+    /// Uses `PROFILE`, `OPT_LEVEL` and `DEBUG` environment variables, that Cargo provides to `build.rs` scripts.
+    /// Besides choosing between [`Profile::Debug`](enum.Profile.html#variant.Debug)
+    /// and [`Profile::Release`](enum.Profile.html#variant.Release),
+    /// optimization level and debug info settings are propagated to the device crate build.
+    ///
+    /// Missing variables are ignored, so it's safe to be used outside of `build.rs` scripts.
+    ///
+    /// # Usage
+    /// ``` no_run
+    /// use ptx_builder::prelude::*;
+    /// # use ptx_builder::error::Result;
+    ///
+    /// # fn main() -> Result<()> {
+    /// Builder::new(".")?
+    ///     .set_profile_from_env()
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_profile_from_env(mut self) -> Self {
+        match env::var("PROFILE").as_ref().map(String::as_str) {
+            Ok("debug") => self.profile = Profile::Debug,
+            Ok("release") => self.profile = Profile::Release,
+            _ => {}
+        }
+
+        self.opt_level = env::var("OPT_LEVEL").ok();
+        self.debug_info = env::var("DEBUG").ok();
+        self
+    }
+
     /// Set crate type that needs to be built.
     ///
     /// Mandatory for mixed crates - that have both `lib.rs` and `main.rs`,
@@ -165,8 +299,310 @@ impl Builder {
         self
     }
 
+    /// Set name of the target that needs to be built, e.g. one of `[[bin]]` entries.
+    ///
+    /// When crate type is not specified, it's derived from the target.
+    ///
+    /// # Usage
+    /// ``` no_run
+    /// use ptx_builder::prelude::*;
+    /// # use ptx_builder::error::Result;
+    ///
+    /// # fn main() -> Result<()> {
+    /// Builder::new(".")?
+    ///     .set_crate_type(CrateType::Binary)
+    ///     .set_target_name("reduction-kernels")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_target_name<S: AsRef<str>>(mut self, name: S) -> Self {
+        self.target_name = Some(name.as_ref().to_string());
+        self
+    }
+
+    /// Set GPU architecture the assembly is generated for, e.g. `sm_61`.
+    ///
+    /// When not specified, LLVM default (`sm_30`) is used.
+    ///
+    /// # Usage
+    /// ``` no_run
+    /// use ptx_builder::prelude::*;
+    /// # use ptx_builder::error::Result;
+    ///
+    /// # fn main() -> Result<()> {
+    /// Builder::new(".")?
+    ///     .set_target_cpu("sm_61")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_target_cpu<S: AsRef<str>>(mut self, target_cpu: S) -> Self {
+        self.target_cpu = Some(target_cpu.as_ref().to_string());
+        self
+    }
+
+    /// Enable LLVM target feature, e.g. `+ptx60`.
+    ///
+    /// Features without explicit `+` or `-` sign are enabled.
+    ///
+    /// # Usage
+    /// ``` no_run
+    /// use ptx_builder::prelude::*;
+    /// # use ptx_builder::error::Result;
+    ///
+    /// # fn main() -> Result<()> {
+    /// Builder::new(".")?
+    ///     .add_target_feature("+ptx60")
+    ///     .add_target_feature("+sm_70")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_target_feature<S: AsRef<str>>(mut self, feature: S) -> Self {
+        let feature = feature.as_ref();
+
+        if feature.starts_with('+') || feature.starts_with('-') {
+            self.target_features.push(feature.to_string());
+        } else {
+            self.target_features.push(format!("+{}", feature));
+        }
+
+        self
+    }
+
+    /// Append an arbitrary flag to `rustc` invocation of the device crate.
+    ///
+    /// # Usage
+    /// ``` no_run
+    /// use ptx_builder::prelude::*;
+    /// # use ptx_builder::error::Result;
+    ///
+    /// # fn main() -> Result<()> {
+    /// Builder::new(".")?
+    ///     .add_rustc_flag("-Copt-level=3")
+    ///     .add_rustc_flag("-Cllvm-args=--nvptx-prec-divf32=0")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_rustc_flag<S: AsRef<str>>(mut self, flag: S) -> Self {
+        self.rustc_flags.push(flag.as_ref().to_string());
+        self
+    }
+
+    /// Require the assembly to provide `.visible .entry` kernel with the `name`.
+    ///
+    /// Once any kernel is expected, the build fails when the assembly misses one of them,
+    /// or when it provides a kernel that wasn't expected.
+    ///
+    /// # Usage
+    /// ``` no_run
+    /// use ptx_builder::prelude::*;
+    /// # use ptx_builder::error::Result;
+    ///
+    /// # fn main() -> Result<()> {
+    /// Builder::new(".")?
+    ///     .add_expected_kernel("add_vectors")
+    ///     .add_expected_kernel("scale_vector")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_expected_kernel<S: AsRef<str>>(mut self, name: S) -> Self {
+        self.expected_kernels.push(name.as_ref().to_string());
+        self
+    }
+
+    /// Set `rustup` toolchain that builds the device crate, e.g. `nightly-2019-12-01`.
+    ///
+    /// When not specified, the toolchain from device crate's `rust-toolchain` file is used, if any.
+    /// Otherwise, `cargo` from `PATH` builds the crate.
+    ///
+    /// # Usage
+    /// ``` no_run
+    /// use ptx_builder::prelude::*;
+    /// # use ptx_builder::error::Result;
+    ///
+    /// # fn main() -> Result<()> {
+    /// Builder::new(".")?
+    ///     .set_toolchain("nightly-2019-12-01")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_toolchain<S: AsRef<str>>(mut self, toolchain: S) -> Self {
+        self.toolchain = Some(toolchain.as_ref().to_string());
+        self
+    }
+
+    /// Require the device crate toolchain to be a nightly not older than `date`.
+    ///
+    /// Checked before the build and reported with
+    /// [`BuildErrorKind::CommandCommitDateNotFulfilled`](../error/enum.BuildErrorKind.html#variant.CommandCommitDateNotFulfilled).
+    ///
+    /// # Usage
+    /// ``` no_run
+    /// use ptx_builder::prelude::*;
+    /// # use ptx_builder::error::Result;
+    ///
+    /// # fn main() -> Result<()> {
+    /// Builder::new(".")?
+    ///     .set_required_nightly_date("2019-12-01".parse()?)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_required_nightly_date(mut self, date: CommitDate) -> Self {
+        self.required_nightly_date = Some(date);
+        self
+    }
+
+    /// Place build artifacts into `out_dir`, e.g. `OUT_DIR` of the host crate,
+    /// instead of a shared temporary location.
+    ///
+    /// The device crate is built in `ptx-builder` subdirectory, and the final assembly
+    /// is copied to `out_dir` under a deterministic name: after the crate target,
+    /// GPU architecture, if specified, and the cache key of the build settings,
+    /// e.g. `kernels-0123456789abcdef.ptx` or `kernels-sm_61-0123456789abcdef.ptx`.
+    /// Cargo removes the artifacts with `cargo clean` then.
+    pub fn set_out_dir<P: AsRef<Path>>(mut self, out_dir: P) -> Self {
+        self.out_dir = Some(out_dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Set location, where device crates are built and cached.
+    ///
+    /// When not specified, `PTX_BUILDER_CACHE_DIR` environment variable is used,
+    /// falling back to system temporary directory.
+    /// Stale builds can be removed with [`Cache`](../cache/struct.Cache.html).
+    ///
+    /// # Usage
+    /// ``` no_run
+    /// use ptx_builder::prelude::*;
+    /// # use ptx_builder::error::Result;
+    ///
+    /// # fn main() -> Result<()> {
+    /// Builder::new(".")?
+    ///     .set_cache_root("/var/cache/ptx-builder")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_cache_root<P: AsRef<Path>>(mut self, root: P) -> Self {
+        self.cache_root = Some(root.as_ref().to_path_buf());
+        self
+    }
+
+    /// Set how long to wait for a concurrent build of the same configuration,
+    /// e.g. from another `build.rs` script. 10 minutes by default.
+    ///
+    /// Expired timeout is reported with
+    /// [`BuildErrorKind::LockTimeout`](../error/enum.BuildErrorKind.html#variant.LockTimeout).
+    pub fn set_lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = timeout;
+        self
+    }
+
+    /// Set size of `.local` memory in bytes, that is reported as too large stack frame
+    /// by [`BuildOutput::lint`](struct.BuildOutput.html#method.lint). 1 KiB by default.
+    pub fn set_stack_frame_limit(mut self, bytes: usize) -> Self {
+        self.stack_frame_limit = bytes;
+        self
+    }
+
+    /// Returns the key of build cache entry for `target_cpu`.
+    ///
+    /// Every build setting, that affects produced artifacts, is a part of the key.
+    pub fn get_cache_key(&self, target_cpu: Option<&str>) -> CacheKey {
+        let crate_path = self.source_crate.get_path();
+        let crate_path = crate_path
+            .canonicalize()
+            .unwrap_or_else(|_| crate_path.into());
+
+        CacheKey::new()
+            .with("crate-path", crate_path.display())
+            .with("package-id", self.source_crate.get_package_id())
+            .with("target", TARGET_NAME)
+            .with("target-cpu", target_cpu.unwrap_or_default())
+            .with("profile", &self.profile)
+            .with("opt-level", self.opt_level.as_deref().unwrap_or_default())
+            .with("debug-info", self.debug_info.as_deref().unwrap_or_default())
+            .with("target-features", self.target_features.join(","))
+            .with("rustc-flags", self.rustc_flags.join(" "))
+            .with("features", self.features.join(","))
+            .with("all-features", self.all_features)
+            .with("no-default-features", self.no_default_features)
+            .with("toolchain", self.get_toolchain().unwrap_or_default())
+    }
+
+    /// Returns build cache of the builder.
+    pub fn get_cache(&self) -> Cache {
+        match self.cache_root {
+            Some(ref root) => Cache::new(root),
+            None => Cache::from_env(),
+        }
+    }
+
+    /// Place build artifacts into `OUT_DIR`, that Cargo provides to `build.rs` scripts.
+    ///
+    /// Behaves the same way as [`set_out_dir`](#method.set_out_dir) does.
+    /// Missing variable is ignored, so it's safe to be used outside of `build.rs` scripts.
+    ///
+    /// # Usage
+    /// ``` no_run
+    /// use ptx_builder::prelude::*;
+    /// # use ptx_builder::error::Result;
+    ///
+    /// # fn main() -> Result<()> {
+    /// Builder::new(".")?
+    ///     .set_out_dir_from_env()
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_out_dir_from_env(mut self) -> Self {
+        if let Some(out_dir) = env::var_os("OUT_DIR") {
+            self.out_dir = Some(PathBuf::from(out_dir));
+        }
+
+        self
+    }
+
+    /// Enable Cargo feature of the device crate.
+    ///
+    /// # Usage
+    /// ``` no_run
+    /// use ptx_builder::prelude::*;
+    /// # use ptx_builder::error::Result;
+    ///
+    /// # fn main() -> Result<()> {
+    /// Builder::new(".")?
+    ///     .disable_default_features()
+    ///     .add_feature("double-precision")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_feature<S: AsRef<str>>(mut self, feature: S) -> Self {
+        self.features.push(feature.as_ref().to_string());
+        self
+    }
+
+    /// Enable all Cargo features of the device crate: an equivalent for `--all-features` flag.
+    pub fn enable_all_features(mut self) -> Self {
+        self.all_features = true;
+        self
+    }
+
+    /// Disable `default` Cargo feature of the device crate: an equivalent for `--no-default-features` flag.
+    pub fn disable_default_features(mut self) -> Self {
+        self.no_default_features = true;
+        self
+    }
+
     /// Performs an actual build: runs `cargo` with proper flags and environment.
-    pub fn build(&self) -> Result<BuildStatus> {
+    pub fn build(&self) -> Result<BuildStatus<'_>> {
         if !Self::is_build_needed() {
             return Ok(BuildStatus::NotNeeded);
         }
@@ -174,13 +610,72 @@ impl Builder {
         // Verify `ptx-linker` version.
         ExecutableRunner::new(Linker).with_args(vec!["-V"]).run()?;
 
-        let mut cargo = ExecutableRunner::new(Cargo);
+        Ok(BuildStatus::Success(
+            self.build_assembly(self.target_cpu.as_deref())?,
+        ))
+    }
+
+    /// Performs a build for every specified GPU architecture.
+    ///
+    /// Each architecture gets its own PTX assembly, while the rest of
+    /// build configuration stays the same:
+    /// ``` no_run
+    /// use ptx_builder::prelude::*;
+    /// # use ptx_builder::error::Result;
+    ///
+    /// # fn main() -> Result<()> {
+    /// let builder = Builder::new(".")?;
+    ///
+    /// if let MultiBuildStatus::Success(output) = builder.build_for_target_cpus(["sm_35", "sm_61"])? {
+    ///     for (target_cpu, path) in output.get_assembly_paths() {
+    ///         println!("{}: {}", target_cpu, path.display());
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn build_for_target_cpus<I, S>(&self, target_cpus: I) -> Result<MultiBuildStatus<'_>>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        if !Self::is_build_needed() {
+            return Ok(MultiBuildStatus::NotNeeded);
+        }
+
+        // Verify `ptx-linker` version.
+        ExecutableRunner::new(Linker).with_args(vec!["-V"]).run()?;
+
+        let mut outputs = BTreeMap::new();
+
+        for target_cpu in target_cpus {
+            let target_cpu = target_cpu.as_ref();
+
+            outputs.insert(
+                target_cpu.to_string(),
+                self.build_assembly(Some(target_cpu))?,
+            );
+        }
+
+        Ok(MultiBuildStatus::Success(MultiBuildOutput { outputs }))
+    }
+
+    fn build_assembly(&self, target_cpu: Option<&str>) -> Result<BuildOutput<'_>> {
         let mut args = Vec::new();
 
         args.push("rustc");
 
-        if self.profile == Profile::Release {
-            args.push("--release");
+        match self.profile {
+            Profile::Debug => {}
+
+            Profile::Release => {
+                args.push("--release");
+            }
+
+            Profile::Custom(ref name) => {
+                args.push("--profile");
+                args.push(name);
+            }
         }
 
         args.push("--color");
@@ -189,98 +684,276 @@ impl Builder {
         args.push("--target");
         args.push(TARGET_NAME);
 
-        match self.crate_type {
-            Some(CrateType::Binary) => {
+        if self.source_crate.get_workspace_root().is_some() {
+            args.push("--package");
+            args.push(self.source_crate.get_name());
+        }
+
+        let target = {
+            self.source_crate
+                .get_target(self.crate_type, self.target_name.as_deref())?
+        };
+
+        let crate_type = target.get_crate_type();
+
+        match crate_type {
+            CrateType::Binary => {
                 args.push("--bin");
-                args.push(self.source_crate.get_name());
+                args.push(target.get_name());
             }
 
-            Some(CrateType::Library) => {
+            CrateType::Example => {
+                args.push("--example");
+                args.push(target.get_name());
+            }
+
+            CrateType::Library => {
+                // Cargo reports proper artifact filenames only when it knows the crate type.
                 args.push("--lib");
+                args.push("--crate-type");
+                args.push("cdylib");
             }
+        }
 
-            _ => {}
+        let features = self.features.join(",");
+
+        if !self.features.is_empty() {
+            args.push("--features");
+            args.push(&features);
+        }
+
+        if self.all_features {
+            args.push("--all-features");
+        }
+
+        if self.no_default_features {
+            args.push("--no-default-features");
         }
 
+        args.push("--message-format");
+        args.push(if self.colors {
+            "json-diagnostic-rendered-ansi"
+        } else {
+            "json"
+        });
+
         args.push("-v");
         args.push("--");
-        args.push("--crate-type");
-        args.push("cdylib");
+
+        if crate_type != CrateType::Library {
+            args.push("--crate-type");
+            args.push("cdylib");
+        }
+
         args.push("-Zcrate-attr=no_main");
 
+        let target_cpu_flag = target_cpu.map(|target_cpu| format!("target-cpu={}", target_cpu));
+
+        if let Some(ref flag) = target_cpu_flag {
+            args.push("-C");
+            args.push(flag);
+        }
+
+        let target_features_flag = format!("target-feature={}", self.target_features.join(","));
+
+        if !self.target_features.is_empty() {
+            args.push("-C");
+            args.push(&target_features_flag);
+        }
+
+        for flag in &self.rustc_flags {
+            args.push(flag);
+        }
+
+        let output_root = match self.out_dir {
+            Some(ref out_dir) => out_dir.join("ptx-builder"),
+            None => self.get_cache().get_root().to_path_buf(),
+        };
+
         let output_path = {
             self.source_crate
-                .get_output_path()
+                .get_output_path(&output_root, &self.get_cache_key(target_cpu))
                 .context("Unable to create output path")?
         };
 
+        // Concurrent builds of the same configuration would otherwise share the directory.
+        let _lock = Cache::lock(&output_path, self.lock_timeout)?;
+
+        Cache::touch(&output_path)?;
+
+        let mut cargo = match self.get_toolchain() {
+            Some(toolchain) => {
+                let mut cargo =
+                    ExecutableRunner::new(self.get_cargo(ToolchainCargo::new(toolchain)));
+
+                // Otherwise, host crate's `rustc` would be used instead of the toolchain one.
+                cargo.without_env("RUSTC").without_env("RUSTDOC");
+                cargo
+            }
+
+            None => ExecutableRunner::new(self.get_cargo(ToolchainCargo::current())),
+        };
+
         cargo
             .with_args(&args)
             .with_cwd(self.source_crate.get_path())
             .with_env("PTX_CRATE_BUILDING", "1")
-            .with_env("CARGO_TARGET_DIR", output_path.clone());
-
-        let cargo_output = cargo.run().map_err(|error| match error.kind() {
-            BuildErrorKind::CommandFailed { stderr, .. } => {
-                let lines = stderr
-                    .trim_matches('\n')
-                    .split('\n')
-                    .filter(Self::output_is_not_verbose)
-                    .map(String::from)
-                    .collect();
-
-                Error::from(BuildErrorKind::BuildFailed(lines))
+            .with_env("CARGO_TARGET_DIR", &output_path);
+
+        if let Some(ref opt_level) = self.opt_level {
+            cargo.with_env(self.profile.get_env_var_name("OPT_LEVEL"), opt_level);
+        }
+
+        if let Some(ref debug_info) = self.debug_info {
+            cargo.with_env(self.profile.get_env_var_name("DEBUG"), debug_info);
+        }
+
+        let (status, cargo_output) = cargo.run_with_status()?;
+        let messages = Messages::parse(&cargo_output.stdout);
+
+        if !status.success() {
+            if messages.has_errors() {
+                bail!(BuildErrorKind::CompilationFailed(
+                    messages.into_diagnostics()
+                ));
             }
 
-            _ => error,
-        })?;
+            let lines = cargo_output
+                .stderr
+                .trim_matches('\n')
+                .split('\n')
+                .filter(Self::output_is_not_verbose)
+                .map(String::from)
+                .collect();
 
-        Ok(BuildStatus::Success(
-            self.prepare_output(output_path, &cargo_output.stderr)?,
-        ))
+            bail!(BuildErrorKind::BuildFailed(lines));
+        }
+
+        let output = self.prepare_output(&messages, target, target_cpu)?;
+
+        self.check_kernels(&output)?;
+        Ok(output)
     }
 
-    fn prepare_output(&self, output_path: PathBuf, cargo_stderr: &str) -> Result<BuildOutput> {
-        lazy_static! {
-            static ref SUFFIX_REGEX: Regex =
-                Regex::new(r"-C extra-filename=([\S]+)").expect("Unable to parse regex...");
+    fn check_kernels(&self, output: &BuildOutput) -> Result<()> {
+        if self.expected_kernels.is_empty() {
+            return Ok(());
         }
 
-        let crate_name = self.source_crate.get_output_file_prefix();
+        let module = output.get_module()?;
+        let kernels: Vec<&str> = {
+            module
+                .kernels
+                .iter()
+                .filter(|kernel| kernel.linkage == Linkage::Visible)
+                .map(|kernel| kernel.name.as_str())
+                .collect()
+        };
 
-        // We need the build command to get real output filename.
-        let build_command = {
-            cargo_stderr
-                .trim_matches('\n')
-                .split('\n')
-                .find(|line| {
-                    line.contains(&format!("--crate-name {}", crate_name))
-                        && line.contains("--crate-type cdylib")
+        let missing: Vec<String> = {
+            self.expected_kernels
+                .iter()
+                .filter(|name| !kernels.contains(&name.as_str()))
+                .cloned()
+                .collect()
+        };
+
+        let unexpected: Vec<String> = {
+            kernels
+                .into_iter()
+                .filter(|name| {
+                    !self
+                        .expected_kernels
+                        .iter()
+                        .any(|expected| expected == name)
                 })
-                .map(|line| BuildCommand::Realtime(line.to_string()))
-                .or_else(|| Self::load_cached_build_command(&output_path))
-                .ok_or_else(|| {
-                    Error::from(BuildErrorKind::InternalError(String::from(
-                        "Unable to find build command of the device crate",
-                    )))
-                })?
+                .map(String::from)
+                .collect()
         };
 
-        if let BuildCommand::Realtime(ref command) = build_command {
-            Self::store_cached_build_command(&output_path, &command)?;
+        if !missing.is_empty() || !unexpected.is_empty() {
+            bail!(BuildErrorKind::KernelsMismatch {
+                missing,
+                unexpected
+            });
         }
 
-        let file_suffix = match SUFFIX_REGEX.captures(&build_command) {
-            Some(caps) => caps[1].to_string(),
+        Ok(())
+    }
+
+    fn get_cargo(&self, cargo: ToolchainCargo) -> ToolchainCargo {
+        match self.required_nightly_date {
+            Some(date) => cargo.with_required_commit_date(date),
+            None => cargo,
+        }
+    }
+
+    fn get_toolchain(&self) -> Option<&str> {
+        self.toolchain
+            .as_deref()
+            .or_else(|| self.source_crate.get_toolchain())
+    }
+
+    fn prepare_output(
+        &self,
+        messages: &Messages,
+        target: &Target,
+        target_cpu: Option<&str>,
+    ) -> Result<BuildOutput<'_>> {
+        let target_name = target.get_name();
+
+        let artifact = match messages.find_artifact(target.get_src_path(), target_name, "ptx") {
+            Some(artifact) => artifact,
 
             None => {
                 bail!(BuildErrorKind::InternalError(String::from(
-                    "Unable to find `extra-filename` rustc flag",
+                    "Unable to find PTX artifact of the device crate",
                 )));
             }
         };
 
-        Ok(BuildOutput::new(self, output_path, file_suffix))
+        let artifact_path = match artifact.find_file("ptx") {
+            Some(path) => path.to_path_buf(),
+            None => unreachable!("artifact is expected to have PTX file"),
+        };
+
+        let assembly_path = match self.out_dir {
+            Some(ref out_dir) => {
+                // Builds of the same crate with different settings can share `out_dir`.
+                let cache_key = self.get_cache_key(target_cpu);
+                let assembly_path = out_dir.join(match target_cpu {
+                    Some(target_cpu) => format!("{}-{}-{}.ptx", target_name, target_cpu, cache_key),
+                    None => format!("{}-{}.ptx", target_name, cache_key),
+                });
+
+                let contents = read(&artifact_path).with_context(|_| {
+                    BuildErrorKind::InternalError(format!(
+                        "Unable to read assembly: {}",
+                        artifact_path.display()
+                    ))
+                })?;
+
+                Cache::write_atomically(&assembly_path, &contents).with_context(|_| {
+                    BuildErrorKind::InternalError(format!(
+                        "Unable to copy assembly to: {}",
+                        assembly_path.display()
+                    ))
+                })?;
+
+                assembly_path
+            }
+
+            None => artifact_path.clone(),
+        };
+
+        Ok(BuildOutput::new(
+            self,
+            artifact_path,
+            assembly_path,
+            artifact.fresh,
+            target_name,
+            target_cpu,
+        ))
     }
 
     fn output_is_not_verbose(line: &&str) -> bool {
@@ -290,31 +963,34 @@ impl Builder {
             && !line.starts_with("Caused by:")
             && !line.starts_with("  process didn\'t exit successfully: ")
     }
-
-    fn load_cached_build_command(output_path: &Path) -> Option<BuildCommand> {
-        match read_to_string(output_path.join(LAST_BUILD_CMD)) {
-            Ok(contents) => Some(BuildCommand::Cached(contents)),
-            Err(_) => None,
-        }
-    }
-
-    fn store_cached_build_command(output_path: &Path, command: &str) -> Result<()> {
-        write(output_path.join(LAST_BUILD_CMD), command.as_bytes())
-            .context(BuildErrorKind::OtherError)?;
-
-        Ok(())
-    }
 }
 
 impl<'a> BuildOutput<'a> {
-    fn new(builder: &'a Builder, output_path: PathBuf, file_suffix: String) -> Self {
+    fn new(
+        builder: &'a Builder,
+        artifact_path: PathBuf,
+        assembly_path: PathBuf,
+        fresh: bool,
+        target_name: &str,
+        target_cpu: Option<&str>,
+    ) -> Self {
         BuildOutput {
             builder,
-            output_path,
-            file_suffix,
+            artifact_path,
+            assembly_path,
+            fresh,
+            target_name: target_name.to_string(),
+            target_cpu: target_cpu.map(String::from),
         }
     }
 
+    /// Returns GPU architecture the assembly was generated for.
+    ///
+    /// `None` means LLVM default architecture was used.
+    pub fn get_target_cpu(&self) -> Option<&str> {
+        self.target_cpu.as_deref()
+    }
+
     /// Returns path to PTX assembly file.
     ///
     /// # Usage
@@ -335,15 +1011,174 @@ impl<'a> BuildOutput<'a> {
     /// # }
     /// ```
     pub fn get_assembly_path(&self) -> PathBuf {
-        self.output_path
-            .join(TARGET_NAME)
-            .join(self.builder.profile.to_string())
-            .join("deps")
-            .join(format!(
-                "{}{}.ptx",
-                self.builder.source_crate.get_output_file_prefix(),
-                self.file_suffix,
+        self.assembly_path.clone()
+    }
+
+    /// Returns path to NUL-terminated copy of the assembly, that is placed next to it.
+    pub fn get_nul_terminated_assembly_path(&self) -> PathBuf {
+        self.assembly_path.with_extension("nul.ptx")
+    }
+
+    /// Writes NUL-terminated copy of the assembly, that CUDA driver accepts as is,
+    /// and returns its path (see [`get_nul_terminated_assembly_path`](#method.get_nul_terminated_assembly_path)).
+    ///
+    /// The copy can later be used **in host crate** without allocations or runtime checks:
+    /// ```ignore
+    /// use std::ffi::CStr;
+    ///
+    /// static PTX: &[u8] = include_bytes!(env!("PTX_PATH_CSTR"));
+    ///
+    /// let ptx = unsafe { CStr::from_bytes_with_nul_unchecked(PTX) };
+    /// ```
+    pub fn write_nul_terminated_assembly(&self) -> Result<PathBuf> {
+        let mut contents = read(&self.assembly_path).with_context(|_| {
+            BuildErrorKind::InternalError(format!(
+                "Unable to read assembly: {}",
+                self.assembly_path.display()
             ))
+        })?;
+
+        if contents.contains(&0) {
+            bail!(BuildErrorKind::InternalError(String::from(
+                "Assembly unexpectedly contains NUL byte",
+            )));
+        }
+
+        contents.push(0);
+
+        let nul_terminated_path = self.get_nul_terminated_assembly_path();
+
+        Cache::write_atomically(&nul_terminated_path, &contents)?;
+        Ok(nul_terminated_path)
+    }
+
+    /// Returns parsed PTX assembly: its directives, kernels and variables.
+    ///
+    /// # Usage
+    /// ```no_run
+    /// use ptx_builder::prelude::*;
+    /// # use ptx_builder::error::Result;
+    ///
+    /// # fn main() -> Result<()> {
+    /// if let BuildStatus::Success(output) = Builder::new(".")?.build()? {
+    ///     assert!(output.get_module()?.get_kernel("the_kernel").is_some());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_module(&self) -> Result<Module> {
+        Module::from_file(&self.assembly_path)
+    }
+
+    /// Writes a Rust module with the PTX assembly and typed descriptors of its kernels.
+    ///
+    /// The module can later be used **in host crate**:
+    /// ```ignore
+    /// include!(env!("PTX_PATH_BINDINGS"));
+    ///
+    /// let ptx = CString::new(PTX)?;
+    ///
+    /// for kernel in KERNELS {
+    ///     println!("{}: {:?}", kernel.name, kernel.param_types);
+    /// }
+    ///
+    /// assert_eq!(kernels::THE_KERNEL.param_count, 2);
+    /// ```
+    pub fn write_bindings<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let contents = bindings::generate(&self.get_module()?, &self.assembly_path);
+
+        write(path, contents.as_bytes()).context(BuildErrorKind::OtherError)?;
+        Ok(())
+    }
+
+    /// Returns path to JSON manifest of the assembly, that is placed next to it.
+    pub fn get_kernel_manifest_path(&self) -> PathBuf {
+        self.assembly_path.with_extension("json")
+    }
+
+    /// Writes JSON manifest, that describes the assembly for tools outside of Rust,
+    /// and returns its path (see [`get_kernel_manifest_path`](#method.get_kernel_manifest_path)):
+    /// ```text
+    /// {
+    ///   "crate": "sample-ptx-crate",
+    ///   "target": "sample_ptx_crate",
+    ///   "target_cpu": "sm_61",
+    ///   "profile": "release",
+    ///   "assembly": "/path/to/sample_ptx_crate.ptx",
+    ///   "ptx": { "version": "6.0", "target": ["sm_61"], "address_size": 64 },
+    ///   "kernels": [
+    ///     {
+    ///       "name": "the_kernel",
+    ///       "linkage": "visible",
+    ///       "params": [
+    ///         { "name": "the_kernel_param_0", "type": ".u64", "align": null, "array_len": null }
+    ///       ],
+    ///       "local_memory": 0
+    ///     }
+    ///   ]
+    /// }
+    /// ```
+    pub fn write_kernel_manifest(&self) -> Result<PathBuf> {
+        let module = self.get_module()?;
+        let manifest_path = self.get_kernel_manifest_path();
+
+        let manifest = KernelManifest {
+            crate_name: self.builder.source_crate.get_name(),
+            target: &self.target_name,
+            target_cpu: self.get_target_cpu(),
+            profile: self.builder.profile.to_string(),
+            assembly: &self.assembly_path,
+
+            ptx: PtxManifest {
+                version: module.version.as_deref(),
+                target: &module.target,
+                address_size: module.address_size,
+            },
+
+            kernels: &module.kernels,
+        };
+
+        let contents =
+            { serde_json::to_string_pretty(&manifest).context("Unable to serialize manifest")? };
+
+        // Other builds of the entry may read the file meanwhile, its lock is already released.
+        Cache::write_atomically(&manifest_path, contents.as_bytes())?;
+        Ok(manifest_path)
+    }
+
+    /// Checks the assembly for suspicious symbols and stack frames,
+    /// see [`Lint`](../lint/enum.Lint.html) for details.
+    ///
+    /// # Usage
+    /// ```no_run
+    /// use ptx_builder::prelude::*;
+    /// # use ptx_builder::error::Result;
+    ///
+    /// # fn main() -> Result<()> {
+    /// if let BuildStatus::Success(output) = Builder::new(".")?.build()? {
+    ///     for lint in output.lint()? {
+    ///         println!("cargo:warning={}", lint);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn lint(&self) -> Result<Vec<Lint>> {
+        Ok(lint::check(
+            &self.get_module()?,
+            self.builder.stack_frame_limit,
+        ))
+    }
+
+    /// Returns the key of build cache entry, that holds the assembly.
+    pub fn get_cache_key(&self) -> CacheKey {
+        self.builder.get_cache_key(self.get_target_cpu())
+    }
+
+    /// Returns `true` when the assembly was actually rebuilt,
+    /// and `false` when Cargo found it up-to-date.
+    pub fn was_rebuilt(&self) -> bool {
+        !self.fresh
     }
 
     /// Returns a list of crate dependencies.
@@ -383,65 +1218,127 @@ impl<'a> BuildOutput<'a> {
             .skip(1)
             .collect::<String>();
 
-        let cargo_deps = vec![
-            self.builder.source_crate.get_path().join("Cargo.toml"),
-            self.builder.source_crate.get_path().join("Cargo.lock"),
-        ];
+        let source_crate = &self.builder.source_crate;
+        let mut cargo_deps = vec![source_crate.get_path().join("Cargo.toml")];
+
+        if let Some(workspace_root) = source_crate.get_workspace_root() {
+            cargo_deps.push(workspace_root.join("Cargo.toml"));
+        }
+
+        cargo_deps.push(source_crate.get_lockfile_path());
 
         Ok(deps_contents
             .trim()
             .split(' ')
             .map(|item| PathBuf::from(item.trim()))
-            .chain(cargo_deps.into_iter())
+            .chain(cargo_deps)
             .collect())
     }
 
     fn get_deps_file_contents(&self) -> Result<String> {
-        let crate_deps_path = self
-            .output_path
-            .join(TARGET_NAME)
-            .join(self.builder.profile.to_string())
-            .join(format!(
-                "{}.d",
-                self.builder
-                    .source_crate
-                    .get_deps_file_prefix(self.builder.crate_type)?
-            ));
+        // Cargo puts deps file next to the artifact.
+        let crate_deps_path = self.artifact_path.with_extension("d");
+
+        Ok(read_to_string(crate_deps_path).context(BuildErrorKind::OtherError)?)
+    }
+}
+
+impl<'a> MultiBuildOutput<'a> {
+    /// Returns build output of the specified GPU architecture.
+    pub fn get(&self, target_cpu: &str) -> Option<&BuildOutput<'a>> {
+        self.outputs.get(target_cpu)
+    }
+
+    /// Returns iterator over GPU architectures and their build outputs.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &BuildOutput<'a>)> {
+        self.outputs
+            .iter()
+            .map(|(target_cpu, output)| (target_cpu.as_str(), output))
+    }
+
+    /// Returns paths to PTX assembly files of every GPU architecture.
+    pub fn get_assembly_paths(&self) -> BTreeMap<String, PathBuf> {
+        self.outputs
+            .iter()
+            .map(|(target_cpu, output)| (target_cpu.clone(), output.get_assembly_path()))
+            .collect()
+    }
 
-        let mut crate_deps_reader =
-            BufReader::new(File::open(crate_deps_path).context(BuildErrorKind::OtherError)?);
+    /// Returns a list of crate dependencies of all the builds.
+    pub fn dependencies(&self) -> Result<Vec<PathBuf>> {
+        let mut dependencies = Vec::new();
+
+        for output in self.outputs.values() {
+            dependencies.extend(output.dependencies()?);
+        }
+
+        dependencies.sort();
+        dependencies.dedup();
+
+        Ok(dependencies)
+    }
+
+    /// Writes a TOML manifest that maps every GPU architecture to its PTX assembly path:
+    /// ```text
+    /// [assemblies]
+    /// sm_35 = "/path/to/sm_35/assembly.ptx"
+    /// sm_61 = "/path/to/sm_61/assembly.ptx"
+    /// ```
+    pub fn write_manifest<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let assemblies = {
+            self.get_assembly_paths()
+                .into_iter()
+                .map(|(target_cpu, path)| {
+                    (target_cpu, toml::Value::String(path.display().to_string()))
+                })
+                .collect()
+        };
 
-        let mut crate_deps_contents = String::new();
+        let mut manifest = toml::value::Table::new();
+        manifest.insert(String::from("assemblies"), toml::Value::Table(assemblies));
 
-        crate_deps_reader
-            .read_to_string(&mut crate_deps_contents)
-            .context(BuildErrorKind::OtherError)?;
+        let contents = {
+            toml::to_string(&toml::Value::Table(manifest))
+                .context("Unable to serialize manifest")?
+        };
 
-        Ok(crate_deps_contents)
+        write(path, contents.as_bytes()).context(BuildErrorKind::OtherError)?;
+        Ok(())
     }
 }
 
-impl fmt::Display for Profile {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl Profile {
+    /// Returns name of Cargo environment variable that overrides the profile `setting`.
+    fn get_env_var_name(&self, setting: &str) -> String {
         match self {
-            Profile::Debug => write!(f, "debug"),
-            Profile::Release => write!(f, "release"),
+            Profile::Debug => format!("CARGO_PROFILE_DEV_{}", setting),
+            Profile::Release => format!("CARGO_PROFILE_RELEASE_{}", setting),
+
+            Profile::Custom(name) => format!(
+                "CARGO_PROFILE_{}_{}",
+                name.to_uppercase().replace("-", "_"),
+                setting
+            ),
         }
     }
 }
 
-enum BuildCommand {
-    Realtime(String),
-    Cached(String),
+impl fmt::Display for CrateType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CrateType::Library => write!(f, "Library"),
+            CrateType::Binary => write!(f, "Binary"),
+            CrateType::Example => write!(f, "Example"),
+        }
+    }
 }
 
-impl std::ops::Deref for BuildCommand {
-    type Target = str;
-
-    fn deref(&self) -> &str {
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            BuildCommand::Realtime(line) => &line,
-            BuildCommand::Cached(line) => &line,
+            Profile::Debug => write!(f, "debug"),
+            Profile::Release => write!(f, "release"),
+            Profile::Custom(name) => write!(f, "{}", name),
         }
     }
 }
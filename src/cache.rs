@@ -0,0 +1,629 @@
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use failure::{Fail, ResultExt};
+use fs2::FileExt;
+
+use crate::error::*;
+
+/// Environment variable, that overrides default cache root.
+pub const CACHE_ROOT_ENV: &str = "PTX_BUILDER_CACHE_DIR";
+
+const LAST_USED_FILE: &str = ".last-used";
+const LOCK_EXTENSION: &str = "lock";
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Location where device crates are built.
+///
+/// Every build configuration of every crate gets its own entry (Cargo target dir),
+/// so stale entries pile up over time and should be pruned.
+///
+/// # Usage
+/// ``` no_run
+/// use std::time::Duration;
+/// use ptx_builder::cache::Cache;
+/// # use ptx_builder::error::Result;
+///
+/// # fn main() -> Result<()> {
+/// let cache = Cache::from_env();
+///
+/// // Remove entries unused for a week...
+/// cache.prune_older_than(Duration::from_secs(7 * 24 * 60 * 60))?;
+///
+/// // ... and keep the cache under 5 GiB.
+/// cache.prune_to_size(5 * 1024 * 1024 * 1024)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Cache {
+    root: PathBuf,
+}
+
+/// Single cache entry - a build location of a device crate.
+#[derive(Clone, Debug)]
+pub struct CacheEntry {
+    path: PathBuf,
+    last_used: SystemTime,
+    size: u64,
+}
+
+/// Stable identifier of a build configuration, that names its cache entry.
+///
+/// The key is FNV-1a 64-bit hash of `name=value\n` lines of every component
+/// (crate path, package, target, profile, flags, features, toolchain, etc.),
+/// so it stays the same across processes and Rust releases.
+///
+/// # Usage
+/// Formatting with `{}` prints the hash, while `{:#}` also lists the components:
+/// ``` no_run
+/// use ptx_builder::prelude::*;
+/// # use ptx_builder::error::Result;
+///
+/// # fn main() -> Result<()> {
+/// let builder = Builder::new(".")?;
+///
+/// eprintln!("{:#}", builder.get_cache_key(Some("sm_61")));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct CacheKey {
+    components: Vec<(String, String)>,
+}
+
+/// Exclusive inter-process lock of a cache entry, released on drop.
+pub(crate) struct EntryLock {
+    file: File,
+}
+
+impl Cache {
+    /// Creates a cache located at `root`.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Cache {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Creates a cache located at `PTX_BUILDER_CACHE_DIR`, when the variable is set,
+    /// or in system temporary directory otherwise.
+    pub fn from_env() -> Self {
+        match env::var_os(CACHE_ROOT_ENV) {
+            Some(ref root) if !root.is_empty() => Self::new(root),
+            _ => Self::new(env::temp_dir().join("ptx-builder-0.5")),
+        }
+    }
+
+    /// Returns cache root path.
+    pub fn get_root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns all cache entries, least recently used first.
+    pub fn entries(&self) -> Result<Vec<CacheEntry>> {
+        let mut entries = Vec::new();
+
+        if !self.root.is_dir() {
+            return Ok(entries);
+        }
+
+        for crate_dir in read_dir_paths(&self.root)? {
+            if !crate_dir.is_dir() {
+                continue;
+            }
+
+            for path in read_dir_paths(&crate_dir)? {
+                if !path.is_dir() {
+                    continue;
+                }
+
+                // Another build could prune the entry meanwhile.
+                if let Some(entry) = CacheEntry::load(path)? {
+                    entries.push(entry);
+                }
+            }
+        }
+
+        entries.sort_by_key(|entry| entry.last_used);
+        Ok(entries)
+    }
+
+    /// Removes entries, that were not used for longer than `age`.
+    ///
+    /// Returns paths of removed entries.
+    pub fn prune_older_than(&self, age: Duration) -> Result<Vec<PathBuf>> {
+        let now = SystemTime::now();
+
+        let stale_entries = {
+            self.entries()?.into_iter().filter(|entry| {
+                now.duration_since(entry.last_used)
+                    .map(|elapsed| elapsed > age)
+                    .unwrap_or(false)
+            })
+        };
+
+        let mut removed = Vec::new();
+
+        for entry in stale_entries {
+            if Self::remove_entry(&entry)? {
+                removed.push(entry.path);
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Removes least recently used entries, until the cache fits into `budget` bytes.
+    ///
+    /// Entries, that are being built right now, are kept and still count towards the budget.
+    ///
+    /// Returns paths of removed entries.
+    pub fn prune_to_size(&self, budget: u64) -> Result<Vec<PathBuf>> {
+        let entries = self.entries()?;
+        let mut total_size: u64 = entries.iter().map(|entry| entry.size).sum();
+        let mut removed = Vec::new();
+
+        for entry in entries {
+            if total_size <= budget {
+                break;
+            }
+
+            if Self::remove_entry(&entry)? {
+                total_size -= entry.size;
+                removed.push(entry.path);
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Records the entry at `path` was just used.
+    pub(crate) fn touch(path: &Path) -> Result<()> {
+        let timestamp = {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .context(BuildErrorKind::OtherError)?
+                .as_secs()
+        };
+
+        // The entry could be pruned after it was created, but before it was locked.
+        fs::create_dir_all(path).context(BuildErrorKind::OtherError)?;
+        fs::write(path.join(LAST_USED_FILE), timestamp.to_string())
+            .context(BuildErrorKind::OtherError)?;
+
+        Ok(())
+    }
+
+    /// Locks the entry at `path`, so concurrent builds don't write there simultaneously.
+    ///
+    /// The lock file lives next to the entry (e.g. `<hash>.lock`), so the entry
+    /// can be removed while the lock is held.
+    /// Waits for other processes to release the entry for at most `timeout`.
+    pub(crate) fn lock(path: &Path, timeout: Duration) -> Result<EntryLock> {
+        let lock_path = get_lock_path(path);
+        let file = File::create(&lock_path).context(BuildErrorKind::OtherError)?;
+        let started = Instant::now();
+
+        loop {
+            match file.try_lock_exclusive() {
+                Ok(()) => break,
+
+                // Only a lock held by another build is worth waiting for.
+                Err(ref error) if is_lock_contended(error) => {}
+
+                Err(error) => {
+                    let kind = BuildErrorKind::InternalError(format!(
+                        "Unable to lock cache entry: {}",
+                        lock_path.display()
+                    ));
+
+                    return Err(error.context(kind).into());
+                }
+            }
+
+            if started.elapsed() >= timeout {
+                bail!(BuildErrorKind::LockTimeout {
+                    path: lock_path,
+                    timeout,
+                });
+            }
+
+            thread::sleep(LOCK_POLL_INTERVAL);
+        }
+
+        Ok(EntryLock { file })
+    }
+
+    /// Writes `contents` into a temporary file and renames it to `path`,
+    /// so concurrent builds never read partially written files.
+    pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+        let temp_path = {
+            let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+
+            file_name.push(format!(
+                ".tmp-{}-{}",
+                process::id(),
+                TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+
+            path.with_file_name(file_name)
+        };
+
+        fs::write(&temp_path, contents).context(BuildErrorKind::OtherError)?;
+
+        let result = fs::rename(&temp_path, path);
+
+        if result.is_err() {
+            fs::remove_file(&temp_path).unwrap_or_default();
+        }
+
+        result.with_context(|_| {
+            BuildErrorKind::InternalError(format!("Unable to write: {}", path.display()))
+        })?;
+
+        Ok(())
+    }
+
+    /// Removes the `entry` unless it's being built right now.
+    ///
+    /// Returns whether the entry was removed.
+    fn remove_entry(entry: &CacheEntry) -> Result<bool> {
+        let _lock = match Self::lock(&entry.path, Duration::from_secs(0)) {
+            Ok(lock) => lock,
+            Err(_) => return Ok(false),
+        };
+
+        // Another process could prune the entry before we got the lock.
+        if !entry.path.is_dir() {
+            return Ok(false);
+        }
+
+        // The lock file itself is kept: removing it would let another process
+        // lock a fresh file, while someone still holds the old one.
+        fs::remove_dir_all(&entry.path).with_context(|_| {
+            BuildErrorKind::InternalError(format!(
+                "Unable to remove cache entry: {}",
+                entry.path.display()
+            ))
+        })?;
+
+        Ok(true)
+    }
+}
+
+impl CacheKey {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn with<V: fmt::Display>(mut self, name: &str, value: V) -> Self {
+        self.components.push((name.to_string(), value.to_string()));
+
+        self
+    }
+
+    /// Returns names and values of the key components.
+    pub fn get_components(&self) -> &[(String, String)] {
+        &self.components
+    }
+
+    /// Returns the key hash.
+    pub fn get_hash(&self) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        let mut hash = FNV_OFFSET_BASIS;
+
+        for (name, value) in &self.components {
+            let line = format!("{}={}\n", name, value);
+
+            for byte in line.bytes() {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(FNV_PRIME);
+            }
+        }
+
+        hash
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.get_hash())?;
+
+        if f.alternate() {
+            for (name, value) in &self.components {
+                write!(f, "\n  {} = {:?}", name, value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for EntryLock {
+    fn drop(&mut self) {
+        // The lock is released when the file is closed anyway.
+        let _ = self.file.unlock();
+    }
+}
+
+impl CacheEntry {
+    /// Loads the entry at `path`, or returns `None` if it doesn't exist (anymore).
+    fn load(path: PathBuf) -> Result<Option<Self>> {
+        let last_used = match fs::read_to_string(path.join(LAST_USED_FILE)) {
+            Ok(contents) => match contents.trim().parse() {
+                Ok(timestamp) => UNIX_EPOCH + Duration::from_secs(timestamp),
+                Err(_) => UNIX_EPOCH,
+            },
+
+            // Entries of older versions don't have the file.
+            Err(_) => match fs::metadata(&path).and_then(|metadata| metadata.modified()) {
+                Ok(modified) => modified,
+                Err(ref error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+                Err(error) => return Err(error.context(BuildErrorKind::OtherError).into()),
+            },
+        };
+
+        let size = get_size(&path)?;
+
+        if !path.is_dir() {
+            return Ok(None);
+        }
+
+        Ok(Some(CacheEntry {
+            path,
+            last_used,
+            size,
+        }))
+    }
+
+    /// Returns entry location.
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns time of the last build, that used the entry.
+    pub fn get_last_used(&self) -> SystemTime {
+        self.last_used
+    }
+
+    /// Returns total size of entry files in bytes.
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+}
+
+fn is_lock_contended(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::WouldBlock
+        || error.raw_os_error() == fs2::lock_contended_error().raw_os_error()
+}
+
+fn get_lock_path(path: &Path) -> PathBuf {
+    path.with_extension(LOCK_EXTENSION)
+}
+
+/// Lists the directory, considering vanished one (e.g. a pruned entry) as empty.
+fn read_dir_paths(path: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => return Ok(paths),
+        Err(error) => return Err(error.context(BuildErrorKind::OtherError).into()),
+    };
+
+    for entry in entries {
+        paths.push(entry.context(BuildErrorKind::OtherError)?.path());
+    }
+
+    Ok(paths)
+}
+
+/// Returns total size of files at `path`, considering vanished ones as empty.
+fn get_size(path: &Path) -> Result<u64> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(error) => return Err(error.context(BuildErrorKind::OtherError).into()),
+    };
+
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    let mut size = 0;
+
+    for path in read_dir_paths(path)? {
+        size += get_size(&path)?;
+    }
+
+    Ok(size)
+}
+
+#[test]
+fn should_provide_stable_cache_key() {
+    assert_eq!(CacheKey::new().get_hash(), 0xcbf2_9ce4_8422_2325);
+
+    let key = CacheKey::new()
+        .with("target-cpu", "sm_61")
+        .with("profile", "release");
+
+    assert_eq!(key.to_string(), "f3926fef984d2631");
+    assert_eq!(
+        format!("{:#}", key),
+        "f3926fef984d2631\n  target-cpu = \"sm_61\"\n  profile = \"release\""
+    );
+
+    assert_ne!(
+        key,
+        CacheKey::new()
+            .with("target-cpu", "sm_35")
+            .with("profile", "release")
+    );
+}
+
+#[test]
+fn should_lock_entries() {
+    let path = env::temp_dir().join("ptx-builder-cache-lock-test");
+    fs::create_dir_all(&path).unwrap();
+
+    let lock = Cache::lock(&path, Duration::from_secs(1)).unwrap();
+
+    match Cache::lock(&path, Duration::from_millis(200))
+        .err()
+        .unwrap()
+        .kind()
+    {
+        BuildErrorKind::LockTimeout {
+            path: lock_path, ..
+        } => {
+            assert_eq!(lock_path, path.with_extension("lock"));
+        }
+
+        _ => unreachable!("it should fail with proper error"),
+    }
+
+    drop(lock);
+    assert!(Cache::lock(&path, Duration::from_secs(0)).is_ok());
+
+    fs::remove_dir_all(&path).unwrap_or_default();
+    fs::remove_file(path.with_extension("lock")).unwrap_or_default();
+}
+
+#[cfg(test)]
+fn create_entry(root: &Path, name: &str, last_used: u64, size: usize) -> PathBuf {
+    let path = root.join("sample_ptx_crate").join(name);
+
+    fs::create_dir_all(&path).unwrap();
+    fs::write(path.join("assembly.ptx"), vec![b'x'; size]).unwrap();
+    fs::write(path.join(LAST_USED_FILE), last_used.to_string()).unwrap();
+
+    path
+}
+
+#[test]
+fn should_prune_old_entries() {
+    let root = env::temp_dir().join("ptx-builder-cache-age-test");
+    fs::remove_dir_all(&root).unwrap_or_default();
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+
+    let stale = create_entry(&root, "stale", now.as_secs() - 3600, 10);
+    let fresh = create_entry(&root, "fresh", now.as_secs(), 10);
+
+    let cache = Cache::new(&root);
+
+    assert_eq!(cache.entries().unwrap().len(), 2);
+    assert_eq!(
+        cache.prune_older_than(Duration::from_secs(60)).unwrap(),
+        vec![stale.clone()]
+    );
+
+    assert!(!stale.exists());
+    assert!(fresh.exists());
+
+    fs::remove_dir_all(&root).unwrap_or_default();
+}
+
+#[test]
+fn should_prune_entries_beyond_size_budget() {
+    let root = env::temp_dir().join("ptx-builder-cache-size-test");
+    fs::remove_dir_all(&root).unwrap_or_default();
+
+    let oldest = create_entry(&root, "oldest", 100, 1000);
+    let older = create_entry(&root, "older", 200, 1000);
+    let newest = create_entry(&root, "newest", 300, 1000);
+
+    let cache = Cache::new(&root);
+
+    let entries = cache.entries().unwrap();
+
+    assert_eq!(entries[0].get_path(), oldest.as_path());
+    assert!(entries[0].get_size() >= 1000);
+
+    assert_eq!(
+        cache.prune_to_size(1500).unwrap(),
+        vec![oldest.clone(), older.clone()]
+    );
+
+    assert!(newest.exists());
+    assert!(cache.prune_to_size(0).unwrap().contains(&newest));
+    assert!(cache.entries().unwrap().is_empty());
+
+    fs::remove_dir_all(&root).unwrap_or_default();
+}
+
+#[test]
+fn should_skip_vanished_entries() {
+    let root = env::temp_dir().join("ptx-builder-cache-vanished-test");
+    fs::remove_dir_all(&root).unwrap_or_default();
+
+    let vanished = create_entry(&root, "vanished", 100, 10);
+    fs::remove_dir_all(&vanished).unwrap();
+
+    assert!(CacheEntry::load(vanished.clone()).unwrap().is_none());
+    assert!(read_dir_paths(&vanished).unwrap().is_empty());
+    assert_eq!(get_size(&vanished).unwrap(), 0);
+
+    assert!(Cache::new(&root).entries().unwrap().is_empty());
+    assert!(Cache::new(&root).prune_to_size(0).unwrap().is_empty());
+
+    fs::remove_dir_all(&root).unwrap_or_default();
+}
+
+#[test]
+fn should_keep_locked_entries_when_pruning() {
+    let root = env::temp_dir().join("ptx-builder-cache-locked-test");
+    fs::remove_dir_all(&root).unwrap_or_default();
+
+    let oldest = create_entry(&root, "oldest", 100, 1000);
+    let older = create_entry(&root, "older", 200, 1000);
+    let newest = create_entry(&root, "newest", 300, 1000);
+
+    let cache = Cache::new(&root);
+    let lock = Cache::lock(&oldest, Duration::from_secs(0)).unwrap();
+
+    // The locked entry still takes its space, so both others have to go.
+    assert_eq!(
+        cache.prune_to_size(1500).unwrap(),
+        vec![older.clone(), newest.clone()]
+    );
+
+    assert!(oldest.exists());
+    assert!(!older.exists());
+    assert!(!newest.exists());
+
+    drop(lock);
+
+    assert_eq!(cache.prune_to_size(0).unwrap(), vec![oldest.clone()]);
+    assert!(Cache::lock(&oldest, Duration::from_secs(0)).is_ok());
+
+    fs::remove_dir_all(&root).unwrap_or_default();
+}
+
+#[test]
+fn should_write_files_atomically() {
+    let root = env::temp_dir().join("ptx-builder-cache-write-test");
+    fs::remove_dir_all(&root).unwrap_or_default();
+    fs::create_dir_all(&root).unwrap();
+
+    let path = root.join("assembly.json");
+
+    Cache::write_atomically(&path, b"first").unwrap();
+    Cache::write_atomically(&path, b"second").unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+    assert_eq!(read_dir_paths(&root).unwrap(), vec![path]);
+
+    fs::remove_dir_all(&root).unwrap_or_default();
+}
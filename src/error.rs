@@ -1,10 +1,14 @@
 use std::fmt;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use colored::*;
 use failure::{Backtrace, Context, Fail};
 use semver::{Version, VersionReq};
 
+use crate::executable::{CommitDate, ToolchainInfo};
+use crate::messages::Diagnostic;
+
 #[macro_export]
 macro_rules! bail {
     ($err:expr) => {
@@ -16,10 +20,10 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub struct Error {
-    inner: Context<BuildErrorKind>,
+    inner: Box<Context<BuildErrorKind>>,
 }
 
-#[derive(Debug, PartialEq, Fail, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum BuildErrorKind {
     CommandNotFound {
         command: String,
@@ -37,15 +41,34 @@ pub enum BuildErrorKind {
         required: VersionReq,
         hint: String,
     },
+    CommandCommitDateNotFulfilled {
+        command: String,
+        current: ToolchainInfo,
+        required: CommitDate,
+        hint: String,
+    },
 
     InvalidCratePath(PathBuf),
+    CrateBuildFailed(PathBuf),
+    LockTimeout {
+        path: PathBuf,
+        timeout: Duration,
+    },
     BuildFailed(Vec<String>),
+    CompilationFailed(Vec<Diagnostic>),
     InvalidCrateType(String),
     MissingCrateType,
+    MissingCrateTarget(String),
+    KernelsMismatch {
+        missing: Vec<String>,
+        unexpected: Vec<String>,
+    },
     InternalError(String),
     OtherError,
 }
 
+impl Fail for BuildErrorKind {}
+
 impl Fail for Error {
     fn name(&self) -> Option<&str> {
         self.inner.name()
@@ -75,21 +98,23 @@ impl Error {
 impl From<BuildErrorKind> for Error {
     fn from(kind: BuildErrorKind) -> Error {
         Error {
-            inner: Context::new(kind),
+            inner: Box::new(Context::new(kind)),
         }
     }
 }
 
 impl From<Context<BuildErrorKind>> for Error {
     fn from(inner: Context<BuildErrorKind>) -> Error {
-        Error { inner }
+        Error {
+            inner: Box::new(inner),
+        }
     }
 }
 
 impl From<Context<String>> for Error {
     fn from(inner: Context<String>) -> Error {
         Error {
-            inner: inner.map(BuildErrorKind::InternalError),
+            inner: Box::new(inner.map(BuildErrorKind::InternalError)),
         }
     }
 }
@@ -138,6 +163,20 @@ impl fmt::Display for BuildErrorKind {
                 hint.underline(),
             ),
 
+            CommandCommitDateNotFulfilled {
+                command,
+                current,
+                required,
+                hint,
+            } => write!(
+                formatter,
+                "Command toolchain is not fulfilled: '{}' is currently '{}' but nightly from '{}' or newer is required. {}.",
+                command.bold(),
+                current.to_string().underline(),
+                required.to_string().underline(),
+                hint.underline(),
+            ),
+
             InvalidCratePath(path) => write!(
                 formatter,
                 "{}: {}",
@@ -145,6 +184,21 @@ impl fmt::Display for BuildErrorKind {
                 path.display()
             ),
 
+            LockTimeout { path, timeout } => write!(
+                formatter,
+                "{}: '{}' is held by another build for longer than {}s",
+                "Unable to lock build directory".bold(),
+                path.display(),
+                timeout.as_secs_f32()
+            ),
+
+            CrateBuildFailed(path) => write!(
+                formatter,
+                "{}: '{}'",
+                "Unable to build device crate".bold(),
+                path.display()
+            ),
+
             BuildFailed(lines) => write!(
                 formatter,
                 "{}\n{}",
@@ -152,6 +206,16 @@ impl fmt::Display for BuildErrorKind {
                 lines.join("\n")
             ),
 
+            CompilationFailed(diagnostics) => {
+                write!(formatter, "{}", "Unable to build a PTX crate!".bold())?;
+
+                for diagnostic in diagnostics {
+                    write!(formatter, "\n{}", diagnostic)?;
+                }
+
+                Ok(())
+            }
+
             InvalidCrateType(crate_type) => write!(
                 formatter,
                 "{}: the crate cannot be build as '{}'",
@@ -165,6 +229,30 @@ impl fmt::Display for BuildErrorKind {
                 "Missing CrateType".bold()
             ),
 
+            MissingCrateTarget(name) => write!(
+                formatter,
+                "{}: the crate has no '{}' target",
+                "Missing crate target".bold(),
+                name
+            ),
+
+            KernelsMismatch {
+                missing,
+                unexpected,
+            } => {
+                write!(formatter, "{}", "Unexpected kernels in the assembly!".bold())?;
+
+                if !missing.is_empty() {
+                    write!(formatter, "\nmissing: '{}'", missing.join("', '"))?;
+                }
+
+                if !unexpected.is_empty() {
+                    write!(formatter, "\nunexpected: '{}'", unexpected.join("', '"))?;
+                }
+
+                Ok(())
+            }
+
             InternalError(message) => write!(formatter, "{}: {}", "Internal error".bold(), message),
             OtherError => write!(formatter, "Other error"),
         }
@@ -5,6 +5,9 @@ use crate::error::*;
 pub mod runner;
 pub use self::runner::{ExecutableRunner, Output};
 
+pub mod toolchain;
+pub use self::toolchain::{Channel, CommitDate, ToolchainInfo};
+
 /// Details and requirements for executables.
 pub trait Executable {
     /// Returns executable name in `PATH`.
@@ -19,6 +22,21 @@ pub trait Executable {
     /// Executable version constraint.
     fn get_required_version(&self) -> Option<VersionReq>;
 
+    /// Minimal nightly commit date, only makes sense for Rust toolchain executables.
+    fn get_required_commit_date(&self) -> Option<CommitDate> {
+        None
+    }
+
+    /// Returns message about how to get a newer nightly toolchain.
+    fn get_commit_date_hint(&self) -> String {
+        self.get_version_hint()
+    }
+
+    /// Returns environment variables the executable has to be run with.
+    fn get_environment(&self) -> Vec<(String, String)> {
+        vec![]
+    }
+
     /// Returns the current version of the executable.
     fn get_current_version(&self) -> Result<Version>
     where
@@ -26,14 +44,73 @@ pub trait Executable {
     {
         self::runner::parse_executable_version(self)
     }
+
+    /// Returns full toolchain details of the executable: version, channel and commit.
+    fn get_current_toolchain(&self) -> Result<ToolchainInfo>
+    where
+        Self: Sized,
+    {
+        self::runner::parse_toolchain_info(self)
+    }
 }
 
 /// `cargo` command.
 pub struct Cargo;
 
+/// `cargo` command of a specific `rustup` toolchain.
+///
+/// The toolchain is selected with `RUSTUP_TOOLCHAIN` environment variable,
+/// so `cargo` in `PATH` has to be a `rustup` proxy.
+///
+/// Additionally, a minimal nightly date can be required:
+/// ```no_run
+/// use ptx_builder::executable::{CommitDate, ExecutableRunner, ToolchainCargo};
+/// # use ptx_builder::error::Result;
+///
+/// # fn main() -> Result<()> {
+/// let cargo = ToolchainCargo::current().with_required_commit_date(CommitDate::new(2019, 12, 1));
+///
+/// ExecutableRunner::new(cargo).with_args(&["build"]).run()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ToolchainCargo {
+    toolchain: Option<String>,
+    required_commit_date: Option<CommitDate>,
+}
+
 /// `ptx-linker` command.
 pub struct Linker;
 
+impl ToolchainCargo {
+    /// Creates `cargo` command of the `toolchain`, e.g. `nightly-2019-12-01`.
+    pub fn new<S: AsRef<str>>(toolchain: S) -> Self {
+        ToolchainCargo {
+            toolchain: Some(toolchain.as_ref().to_string()),
+            required_commit_date: None,
+        }
+    }
+
+    /// Creates `cargo` command of the currently active toolchain.
+    pub fn current() -> Self {
+        ToolchainCargo {
+            toolchain: None,
+            required_commit_date: None,
+        }
+    }
+
+    /// Requires the toolchain to be a nightly built not earlier than `date`.
+    pub fn with_required_commit_date(mut self, date: CommitDate) -> Self {
+        self.required_commit_date = Some(date);
+        self
+    }
+
+    /// Returns the toolchain name.
+    pub fn get_toolchain(&self) -> Option<&str> {
+        self.toolchain.as_deref()
+    }
+}
+
 impl Executable for Cargo {
     fn get_name(&self) -> String {
         String::from("cargo")
@@ -48,17 +125,70 @@ impl Executable for Cargo {
     }
 
     fn get_required_version(&self) -> Option<VersionReq> {
-        Some(VersionReq::parse(">= 1.34.0-nightly").unwrap())
+        // `cargo rustc --crate-type` is required to get proper artifact file names.
+        Some(VersionReq::parse(">= 1.64.0-nightly").unwrap())
     }
 
     fn get_current_version(&self) -> Result<Version> {
-        // Omit Rust channel name because it's not really semver-correct
-        // https://github.com/steveklabnik/semver/issues/105
+        parse_cargo_version(self)
+    }
+}
 
-        self::runner::parse_executable_version(self).map(|mut version| {
-            version.pre = vec![];
-            version
-        })
+impl Executable for ToolchainCargo {
+    fn get_name(&self) -> String {
+        String::from("cargo")
+    }
+
+    fn get_verification_hint(&self) -> String {
+        match self.toolchain {
+            Some(ref toolchain) => format!(
+                "Please make sure you have rustup and '{}' toolchain installed",
+                toolchain
+            ),
+
+            None => Cargo.get_verification_hint(),
+        }
+    }
+
+    fn get_version_hint(&self) -> String {
+        match self.toolchain {
+            Some(ref toolchain) => format!(
+                "Please update '{}' toolchain or choose a newer one",
+                toolchain
+            ),
+
+            None => Cargo.get_version_hint(),
+        }
+    }
+
+    fn get_required_version(&self) -> Option<VersionReq> {
+        Cargo.get_required_version()
+    }
+
+    fn get_required_commit_date(&self) -> Option<CommitDate> {
+        self.required_commit_date
+    }
+
+    fn get_commit_date_hint(&self) -> String {
+        match self.toolchain {
+            Some(ref toolchain) => format!(
+                "Please choose a newer toolchain instead of '{}', e.g. with: 'rustup toolchain install nightly'",
+                toolchain
+            ),
+
+            None => String::from("You can update it with: 'rustup update nightly'"),
+        }
+    }
+
+    fn get_environment(&self) -> Vec<(String, String)> {
+        match self.toolchain {
+            Some(ref toolchain) => vec![(String::from("RUSTUP_TOOLCHAIN"), toolchain.clone())],
+            None => vec![],
+        }
+    }
+
+    fn get_current_version(&self) -> Result<Version> {
+        parse_cargo_version(self)
     }
 }
 
@@ -79,3 +209,15 @@ impl Executable for Linker {
         Some(VersionReq::parse(">= 0.9.0").unwrap())
     }
 }
+
+fn parse_cargo_version<E: Executable>(executable: &E) -> Result<Version> {
+    // Omit Rust channel name because it's not really semver-correct
+    // https://github.com/steveklabnik/semver/issues/105
+
+    executable.get_current_toolchain().map(|toolchain| {
+        let mut version = toolchain.version;
+
+        version.pre = vec![];
+        version
+    })
+}
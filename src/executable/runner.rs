@@ -1,12 +1,12 @@
 use std::ffi::OsStr;
 use std::path::Path;
-use std::process::Command;
+use std::process::{Command, ExitStatus};
 
 use failure::ResultExt;
 use regex::Regex;
 use semver::Version;
 
-use super::Executable;
+use super::{Channel, Executable, ToolchainInfo};
 use crate::error::*;
 
 pub struct ExecutableRunner<Ex: Executable> {
@@ -22,8 +22,12 @@ pub struct Output {
 
 impl<Ex: Executable> ExecutableRunner<Ex> {
     pub fn new(executable: Ex) -> Self {
+        let mut command = Command::new(executable.get_name());
+
+        command.envs(executable.get_environment());
+
         ExecutableRunner {
-            command: Command::new(executable.get_name()),
+            command,
             executable,
         }
     }
@@ -46,6 +50,14 @@ impl<Ex: Executable> ExecutableRunner<Ex> {
         self
     }
 
+    pub fn without_env<K>(&mut self, key: K) -> &mut Self
+    where
+        K: AsRef<OsStr>,
+    {
+        self.command.env_remove(key);
+        self
+    }
+
     pub fn with_cwd<P>(&mut self, path: P) -> &mut Self
     where
         P: AsRef<Path>,
@@ -55,6 +67,21 @@ impl<Ex: Executable> ExecutableRunner<Ex> {
     }
 
     pub fn run(&mut self) -> Result<Output> {
+        let (status, output) = self.run_with_status()?;
+
+        if status.success() {
+            Ok(output)
+        } else {
+            Err(Error::from(BuildErrorKind::CommandFailed {
+                command: self.executable.get_name(),
+                code: status.code().unwrap_or(-1),
+                stderr: output.stderr,
+            }))
+        }
+    }
+
+    /// Runs the command, but leaves exit status handling up to the caller.
+    pub fn run_with_status(&mut self) -> Result<(ExitStatus, Output)> {
         self.check_version()?;
 
         let raw_output = {
@@ -71,15 +98,7 @@ impl<Ex: Executable> ExecutableRunner<Ex> {
             stderr: String::from_utf8(raw_output.stderr).context(BuildErrorKind::OtherError)?,
         };
 
-        if raw_output.status.success() {
-            Ok(output)
-        } else {
-            Err(Error::from(BuildErrorKind::CommandFailed {
-                command: self.executable.get_name(),
-                code: raw_output.status.code().unwrap_or(-1),
-                stderr: output.stderr,
-            }))
-        }
+        Ok((raw_output.status, output))
     }
 
     fn check_version(&self) -> Result<()> {
@@ -88,23 +107,63 @@ impl<Ex: Executable> ExecutableRunner<Ex> {
 
         match required {
             Some(ref required) if !required.matches(&current) => {
-                Err(Error::from(BuildErrorKind::CommandVersionNotFulfilled {
+                bail!(BuildErrorKind::CommandVersionNotFulfilled {
                     command: self.executable.get_name(),
                     current,
                     required: required.clone(),
                     hint: self.executable.get_version_hint(),
-                }))
+                });
             }
 
-            _ => Ok(()),
+            _ => {}
+        }
+
+        if let Some(required) = self.executable.get_required_commit_date() {
+            let current = self.executable.get_current_toolchain()?;
+
+            let is_fulfilled = match (current.channel, current.commit_date) {
+                (Channel::Nightly, Some(date)) | (Channel::Dev, Some(date)) => date >= required,
+                _ => false,
+            };
+
+            if !is_fulfilled {
+                bail!(BuildErrorKind::CommandCommitDateNotFulfilled {
+                    command: self.executable.get_name(),
+                    current,
+                    required,
+                    hint: self.executable.get_commit_date_hint(),
+                });
+            }
         }
+
+        Ok(())
     }
 }
 
 pub(crate) fn parse_executable_version<E: Executable>(executable: &E) -> Result<Version> {
+    let output = get_version_output(executable)?;
+
+    let version_regex = Regex::new(&format!(r"{}\s(\S+)", executable.get_name()))
+        .context(BuildErrorKind::OtherError)?;
+
+    match version_regex.captures(&output) {
+        Some(captures) => Ok(Version::parse(&captures[1]).context(BuildErrorKind::OtherError)?),
+
+        None => Err(Error::from(BuildErrorKind::InternalError(
+            "Unable to find executable version".into(),
+        ))),
+    }
+}
+
+pub(crate) fn parse_toolchain_info<E: Executable>(executable: &E) -> Result<ToolchainInfo> {
+    ToolchainInfo::parse(&executable.get_name(), &get_version_output(executable)?)
+}
+
+fn get_version_output<E: Executable>(executable: &E) -> Result<String> {
     let mut command = Command::new(executable.get_name());
 
-    command.args(&["-V"]);
+    command.args(["-V"]);
+    command.envs(executable.get_environment());
 
     let raw_output = {
         command
@@ -128,14 +187,5 @@ pub(crate) fn parse_executable_version<E: Executable>(executable: &E) -> Result<
         });
     }
 
-    let version_regex = Regex::new(&format!(r"{}\s(\S+)", executable.get_name()))
-        .context(BuildErrorKind::OtherError)?;
-
-    match version_regex.captures(&(output.stdout + &output.stderr)) {
-        Some(captures) => Ok(Version::parse(&captures[1]).context(BuildErrorKind::OtherError)?),
-
-        None => Err(Error::from(BuildErrorKind::InternalError(
-            "Unable to find executable version".into(),
-        ))),
-    }
+    Ok(output.stdout + &output.stderr)
 }
@@ -0,0 +1,259 @@
+use std::fmt;
+use std::str::FromStr;
+
+use failure::ResultExt;
+use lazy_static::*;
+use regex::Regex;
+use semver::{Identifier, Version};
+
+use crate::error::*;
+
+/// Details of Rust toolchain executables, e.g. `cargo` or `rustc`.
+#[derive(PartialEq, Clone, Debug)]
+pub struct ToolchainInfo {
+    /// Full version, including the channel name.
+    pub version: Version,
+
+    /// Release channel.
+    pub channel: Channel,
+
+    /// Short commit hash the executable was built from.
+    pub commit_hash: Option<String>,
+
+    /// Date of the commit the executable was built from.
+    pub commit_date: Option<CommitDate>,
+}
+
+/// Rust release channel.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Channel {
+    Stable,
+    Beta,
+    Nightly,
+    Dev,
+}
+
+/// Date of a toolchain commit.
+///
+/// # Usage
+/// ```
+/// use ptx_builder::executable::CommitDate;
+/// # use ptx_builder::error::Result;
+///
+/// # fn main() -> Result<()> {
+/// let date: CommitDate = "2019-12-01".parse()?;
+///
+/// assert_eq!(date, CommitDate::new(2019, 12, 1));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
+pub struct CommitDate {
+    year: u32,
+    month: u32,
+    day: u32,
+}
+
+impl ToolchainInfo {
+    /// Parses `-V` output of Rust toolchain executables, e.g.:
+    /// ```text
+    /// cargo 1.41.0-nightly (626f0f40e 2019-11-23)
+    /// ```
+    pub fn parse(name: &str, output: &str) -> Result<Self> {
+        let version_regex = {
+            Regex::new(&format!(
+                r"{}\s(\S+)(?:\s\((\S+)\s(\d{{4}}-\d{{2}}-\d{{2}})\))?",
+                regex::escape(name)
+            ))
+            .context(BuildErrorKind::OtherError)?
+        };
+
+        let captures = match version_regex.captures(output) {
+            Some(captures) => captures,
+
+            None => {
+                bail!(BuildErrorKind::InternalError(
+                    "Unable to find executable version".into(),
+                ));
+            }
+        };
+
+        let version = Version::parse(&captures[1]).context(BuildErrorKind::OtherError)?;
+        let channel = Channel::from_version(&version);
+
+        let commit_hash = captures.get(2).map(|hash| hash.as_str().to_string());
+        let commit_date = match captures.get(3) {
+            Some(date) => Some(date.as_str().parse()?),
+            None => None,
+        };
+
+        Ok(ToolchainInfo {
+            version,
+            channel,
+            commit_hash,
+            commit_date,
+        })
+    }
+}
+
+impl Channel {
+    fn from_version(version: &Version) -> Self {
+        match version.pre.first() {
+            None => Channel::Stable,
+
+            Some(Identifier::AlphaNumeric(name)) if name == "nightly" => Channel::Nightly,
+            Some(Identifier::AlphaNumeric(name)) if name == "dev" => Channel::Dev,
+            Some(Identifier::AlphaNumeric(name)) if name.starts_with("beta") => Channel::Beta,
+
+            Some(_) => Channel::Dev,
+        }
+    }
+}
+
+impl CommitDate {
+    /// Creates a date from its components.
+    pub fn new(year: u32, month: u32, day: u32) -> Self {
+        CommitDate { year, month, day }
+    }
+}
+
+impl FromStr for CommitDate {
+    type Err = Error;
+
+    fn from_str(date: &str) -> Result<Self> {
+        lazy_static! {
+            static ref DATE_REGEX: Regex =
+                Regex::new(r"^(\d{4})-(\d{2})-(\d{2})$").expect("Unable to parse regex...");
+        }
+
+        let invalid_date = || {
+            Error::from(BuildErrorKind::InternalError(format!(
+                "Invalid commit date: '{}'",
+                date
+            )))
+        };
+
+        let captures = DATE_REGEX.captures(date.trim()).ok_or_else(invalid_date)?;
+
+        let year = captures[1]
+            .parse::<u32>()
+            .context(BuildErrorKind::OtherError)?;
+        let month = captures[2]
+            .parse::<u32>()
+            .context(BuildErrorKind::OtherError)?;
+        let day = captures[3]
+            .parse::<u32>()
+            .context(BuildErrorKind::OtherError)?;
+
+        let is_leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days_in_month = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if is_leap_year => 29,
+            2 => 28,
+            _ => return Err(invalid_date()),
+        };
+
+        if day < 1 || day > days_in_month {
+            return Err(invalid_date());
+        }
+
+        Ok(CommitDate { year, month, day })
+    }
+}
+
+impl fmt::Display for ToolchainInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.version)?;
+
+        match (&self.commit_hash, &self.commit_date) {
+            (Some(hash), Some(date)) => write!(f, " ({} {})", hash, date),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Channel::Stable => write!(f, "stable"),
+            Channel::Beta => write!(f, "beta"),
+            Channel::Nightly => write!(f, "nightly"),
+            Channel::Dev => write!(f, "dev"),
+        }
+    }
+}
+
+impl fmt::Display for CommitDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+#[test]
+fn should_parse_nightly_toolchain() {
+    let info =
+        ToolchainInfo::parse("cargo", "cargo 1.41.0-nightly (626f0f40e 2019-11-23)\n").unwrap();
+
+    assert_eq!(info.version, Version::parse("1.41.0-nightly").unwrap());
+    assert_eq!(info.channel, Channel::Nightly);
+    assert_eq!(info.commit_hash, Some(String::from("626f0f40e")));
+    assert_eq!(info.commit_date, Some(CommitDate::new(2019, 11, 23)));
+
+    assert_eq!(info.to_string(), "1.41.0-nightly (626f0f40e 2019-11-23)");
+}
+
+#[test]
+fn should_parse_stable_toolchain() {
+    let info = ToolchainInfo::parse("rustc", "rustc 1.39.0 (4560ea788 2019-11-04)\n").unwrap();
+
+    assert_eq!(info.version, Version::parse("1.39.0").unwrap());
+    assert_eq!(info.channel, Channel::Stable);
+    assert_eq!(info.commit_date, Some(CommitDate::new(2019, 11, 4)));
+}
+
+#[test]
+fn should_parse_toolchain_without_commit_info() {
+    let info = ToolchainInfo::parse("rustc", "rustc 1.40.0-beta.3\n").unwrap();
+
+    assert_eq!(info.channel, Channel::Beta);
+    assert_eq!(info.commit_hash, None);
+    assert_eq!(info.commit_date, None);
+}
+
+#[test]
+fn should_compare_commit_dates() {
+    assert!(CommitDate::new(2019, 11, 23) < CommitDate::new(2019, 12, 1));
+    assert!(CommitDate::new(2019, 12, 1) < CommitDate::new(2020, 1, 1));
+
+    assert_eq!(
+        "2019-12-01".parse::<CommitDate>().unwrap(),
+        CommitDate::new(2019, 12, 1)
+    );
+
+    assert!("2019-12".parse::<CommitDate>().is_err());
+}
+
+#[test]
+fn should_reject_invalid_commit_dates() {
+    assert_eq!(
+        "2020-02-29".parse::<CommitDate>().unwrap(),
+        CommitDate::new(2020, 2, 29)
+    );
+
+    for date in &[
+        "2020-13-45",
+        "2020-00-10",
+        "2020-04-31",
+        "2019-02-29",
+        "2020-01-00",
+    ] {
+        match date.parse::<CommitDate>().unwrap_err().kind() {
+            BuildErrorKind::InternalError(message) => {
+                assert_eq!(message, format!("Invalid commit date: '{}'", date));
+            }
+
+            _ => unreachable!("it should fail with proper error"),
+        }
+    }
+}
@@ -51,10 +51,23 @@ pub mod builder;
 /// Build reporting helpers.
 pub mod reporter;
 
+/// Cargo JSON messages parsing.
+pub mod messages;
+
+/// Build cache management.
+pub mod cache;
+
+/// PTX assembly parsing.
+pub mod ptx;
+
+/// Post-build checks of PTX assembly.
+pub mod lint;
+
+mod bindings;
 mod source;
 
 /// Convenient re-exports of mostly used types.
 pub mod prelude {
-    pub use crate::builder::{BuildStatus, Builder, CrateType, Profile};
-    pub use crate::reporter::{CargoAdapter, ErrorLogPrinter};
+    pub use crate::builder::{BuildStatus, Builder, CrateType, MultiBuildStatus, Profile};
+    pub use crate::reporter::{BuildSet, CargoAdapter, ErrorLogPrinter};
 }
@@ -0,0 +1,194 @@
+use std::fmt;
+
+use crate::ptx::{Linkage, Module};
+
+/// Default size of `.local` memory, that is considered as too large stack frame.
+pub const DEFAULT_STACK_FRAME_LIMIT: usize = 1024;
+
+/// Suspicious finding in the PTX assembly.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Lint {
+    /// Visible symbol has Rust-mangled name, e.g. `#[no_mangle]` is missing.
+    MangledSymbol(String),
+
+    /// Visible device function looks like a kernel, e.g. `extern "ptx-kernel"` ABI is missing.
+    KernelLikeFunction(String),
+
+    /// Kernel or function uses more `.local` memory than the limit.
+    LargeStackFrame { name: String, size: usize },
+}
+
+/// Runs all the lints over the `module`.
+///
+/// # Usage
+/// ``` no_run
+/// use ptx_builder::lint::{check, DEFAULT_STACK_FRAME_LIMIT};
+/// use ptx_builder::ptx::Module;
+/// # use ptx_builder::error::Result;
+///
+/// # fn main() -> Result<()> {
+/// let module = Module::from_file("kernels.ptx")?;
+///
+/// for lint in check(&module, DEFAULT_STACK_FRAME_LIMIT) {
+///     println!("cargo:warning={}", lint);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn check(module: &Module, stack_frame_limit: usize) -> Vec<Lint> {
+    let mut lints = Vec::new();
+
+    let visible_names = {
+        module
+            .kernels
+            .iter()
+            .filter(|kernel| kernel.linkage == Linkage::Visible)
+            .map(|kernel| &kernel.name)
+            .chain(
+                module
+                    .functions
+                    .iter()
+                    .filter(|function| function.linkage == Linkage::Visible)
+                    .map(|function| &function.name),
+            )
+            .chain(
+                module
+                    .variables
+                    .iter()
+                    .filter(|variable| variable.linkage == Linkage::Visible)
+                    .map(|variable| &variable.name),
+            )
+    };
+
+    for name in visible_names {
+        if is_mangled(name) {
+            lints.push(Lint::MangledSymbol(name.clone()));
+        }
+    }
+
+    for function in &module.functions {
+        if function.linkage == Linkage::Visible
+            && function.is_defined
+            && function.returns.is_empty()
+            && !is_mangled(&function.name)
+        {
+            lints.push(Lint::KernelLikeFunction(function.name.clone()));
+        }
+    }
+
+    let frames = {
+        module
+            .kernels
+            .iter()
+            .map(|kernel| (&kernel.name, kernel.local_memory))
+            .chain(
+                module
+                    .functions
+                    .iter()
+                    .map(|function| (&function.name, function.local_memory)),
+            )
+    };
+
+    for (name, size) in frames {
+        if size > stack_frame_limit {
+            lints.push(Lint::LargeStackFrame {
+                name: name.clone(),
+                size,
+            });
+        }
+    }
+
+    lints
+}
+
+/// Checks for legacy (`_ZN`) and v0 (`_R`) Rust symbol mangling.
+fn is_mangled(name: &str) -> bool {
+    name.starts_with("_ZN")
+        || (name.starts_with("_R") && name[2..].starts_with(|c: char| c.is_ascii_uppercase()))
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Lint::MangledSymbol(name) => write!(
+                f,
+                "visible symbol '{}' is mangled, is `#[no_mangle]` missing?",
+                name
+            ),
+
+            Lint::KernelLikeFunction(name) => write!(
+                f,
+                "'{}' is exported as a device function, is `extern \"ptx-kernel\"` ABI missing?",
+                name
+            ),
+
+            Lint::LargeStackFrame { name, size } => {
+                write!(
+                    f,
+                    "'{}' uses {} bytes of local memory for its stack frame",
+                    name, size
+                )
+            }
+        }
+    }
+}
+
+#[test]
+fn should_report_suspicious_symbols() {
+    let module = Module::parse(
+        r#"
+.version 6.0
+.target sm_61
+.address_size 64
+
+.visible .entry _ZN10my_kernels10the_kernel17h0123456789abcdefE(
+	.param .u64 param_0
+)
+{
+	ret;
+}
+
+.visible .func add_vectors(
+	.param .b64 add_vectors_param_0
+)
+{
+	ret;
+}
+
+.visible .func  (.param .b32 func_retval0) get_index()
+{
+	ret;
+}
+
+.visible .entry _RNvCs1234_10my_kernels5scale()
+{
+	.local .align 8 .b8 	__local_depot0[2048];
+	ret;
+}
+
+.visible .entry the_kernel()
+{
+	.local .align 8 .b8 	__local_depot1[64];
+	ret;
+}
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        check(&module, DEFAULT_STACK_FRAME_LIMIT),
+        vec![
+            Lint::MangledSymbol(String::from(
+                "_ZN10my_kernels10the_kernel17h0123456789abcdefE"
+            )),
+            Lint::MangledSymbol(String::from("_RNvCs1234_10my_kernels5scale")),
+            Lint::KernelLikeFunction(String::from("add_vectors")),
+            Lint::LargeStackFrame {
+                name: String::from("_RNvCs1234_10my_kernels5scale"),
+                size: 2048,
+            },
+        ]
+    );
+
+    assert_eq!(check(&module, 4096).len(), 3);
+}
@@ -0,0 +1,378 @@
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// Messages that Cargo reports with `--message-format=json` flag.
+#[derive(Debug, Default)]
+pub struct Messages {
+    diagnostics: Vec<Diagnostic>,
+    artifacts: Vec<Artifact>,
+}
+
+/// Files produced by Cargo for a single crate target.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Artifact {
+    /// Cargo package id.
+    pub package_id: String,
+
+    /// Name of the target, e.g. `sample_ptx_crate`.
+    pub name: String,
+
+    /// Path to the target root source file.
+    pub src_path: PathBuf,
+
+    /// Kinds of the target, e.g. `cdylib` or `bin`.
+    pub kind: Vec<String>,
+
+    /// Paths to produced files.
+    pub filenames: Vec<PathBuf>,
+
+    /// `true` when the target was up-to-date and wasn't rebuilt.
+    pub fresh: bool,
+}
+
+/// Compiler diagnostic, e.g. an error or a warning.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Diagnostic {
+    /// Severity of the diagnostic.
+    pub level: DiagnosticLevel,
+
+    /// Diagnostic code, e.g. `E0425`.
+    pub code: Option<String>,
+
+    /// The primary message.
+    pub message: String,
+
+    /// File of the primary span, as reported by the compiler: relative to the workspace root,
+    /// which is the crate root itself unless the device crate is a workspace member,
+    /// or absolute for files outside of the workspace.
+    pub file: Option<PathBuf>,
+
+    /// Line of the primary span, 1-based.
+    pub line: Option<usize>,
+
+    /// Column of the primary span, 1-based.
+    pub column: Option<usize>,
+
+    /// The diagnostic rendered by the compiler, as it would be shown in terminal.
+    pub rendered: Option<String>,
+}
+
+/// Severity of the compiler diagnostic.
+#[derive(PartialEq, Clone, Debug)]
+pub enum DiagnosticLevel {
+    Error,
+    Warning,
+    Note,
+    Help,
+    Other(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+enum RawMessage {
+    CompilerMessage {
+        message: RawDiagnostic,
+    },
+
+    CompilerArtifact {
+        package_id: String,
+        target: RawTarget,
+        filenames: Vec<PathBuf>,
+        fresh: bool,
+    },
+
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct RawTarget {
+    name: String,
+    kind: Vec<String>,
+    src_path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct RawDiagnostic {
+    message: String,
+    level: String,
+    code: Option<RawDiagnosticCode>,
+    spans: Vec<RawDiagnosticSpan>,
+    rendered: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawDiagnosticCode {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct RawDiagnosticSpan {
+    file_name: String,
+    line_start: usize,
+    column_start: usize,
+    is_primary: bool,
+}
+
+impl Messages {
+    /// Parses Cargo output: every line is expected to be a separate JSON message.
+    ///
+    /// Lines that are not messages (e.g. output of build scripts) are ignored.
+    pub fn parse(output: &str) -> Self {
+        let mut messages = Messages::default();
+
+        for line in output.lines() {
+            if !line.starts_with('{') {
+                continue;
+            }
+
+            match serde_json::from_str(line) {
+                Ok(RawMessage::CompilerMessage { message }) => {
+                    messages.diagnostics.push(message.into());
+                }
+
+                Ok(RawMessage::CompilerArtifact {
+                    package_id,
+                    target,
+                    filenames,
+                    fresh,
+                }) => {
+                    messages.artifacts.push(Artifact {
+                        package_id,
+                        name: target.name,
+                        src_path: target.src_path,
+                        kind: target.kind,
+                        filenames,
+                        fresh,
+                    });
+                }
+
+                _ => {}
+            }
+        }
+
+        messages
+    }
+
+    /// Returns all reported diagnostics.
+    pub fn get_diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Returns `true` when at least one error was reported.
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.level == DiagnosticLevel::Error)
+    }
+
+    /// Returns all reported artifacts.
+    pub fn get_artifacts(&self) -> &[Artifact] {
+        &self.artifacts
+    }
+
+    /// Returns the artifact of target `name` with root source file at `src_path`,
+    /// that has a file with `extension`.
+    ///
+    /// Targets are matched by their source file rather than package id,
+    /// because the id format depends on Cargo version.
+    pub fn find_artifact(&self, src_path: &Path, name: &str, extension: &str) -> Option<&Artifact> {
+        self.artifacts.iter().rev().find(|artifact| {
+            artifact.name == name
+                && artifact.has_src_path(src_path)
+                && artifact.find_file(extension).is_some()
+        })
+    }
+
+    /// Consumes messages and returns reported diagnostics.
+    pub fn into_diagnostics(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
+
+impl Artifact {
+    fn has_src_path(&self, src_path: &Path) -> bool {
+        if self.src_path == src_path {
+            return true;
+        }
+
+        match (self.src_path.canonicalize(), src_path.canonicalize()) {
+            (Ok(left), Ok(right)) => left == right,
+            _ => false,
+        }
+    }
+
+    /// Returns the first produced file with `extension`, e.g. `ptx`.
+    pub fn find_file(&self, extension: &str) -> Option<&Path> {
+        self.filenames
+            .iter()
+            .map(PathBuf::as_path)
+            .find(|path| path.extension() == Some(OsStr::new(extension)))
+    }
+}
+
+impl From<RawDiagnostic> for Diagnostic {
+    fn from(raw: RawDiagnostic) -> Self {
+        let primary_span = raw.spans.into_iter().find(|span| span.is_primary);
+
+        Diagnostic {
+            level: DiagnosticLevel::from(raw.level.as_str()),
+            code: raw.code.map(|code| code.code),
+            message: raw.message,
+            file: primary_span
+                .as_ref()
+                .map(|span| PathBuf::from(&span.file_name)),
+            line: primary_span.as_ref().map(|span| span.line_start),
+            column: primary_span.as_ref().map(|span| span.column_start),
+            rendered: raw.rendered,
+        }
+    }
+}
+
+impl<'a> From<&'a str> for DiagnosticLevel {
+    fn from(level: &'a str) -> Self {
+        match level {
+            "error" => DiagnosticLevel::Error,
+            "warning" => DiagnosticLevel::Warning,
+            "note" => DiagnosticLevel::Note,
+            "help" => DiagnosticLevel::Help,
+            other => DiagnosticLevel::Other(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref rendered) = self.rendered {
+            return write!(f, "{}", rendered.trim_end());
+        }
+
+        match self.code {
+            Some(ref code) => write!(f, "{}[{}]: {}", self.level, code, self.message)?,
+            None => write!(f, "{}: {}", self.level, self.message)?,
+        }
+
+        if let (Some(file), Some(line), Some(column)) = (&self.file, self.line, self.column) {
+            write!(f, "\n --> {}:{}:{}", file.display(), line, column)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for DiagnosticLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiagnosticLevel::Error => write!(f, "error"),
+            DiagnosticLevel::Warning => write!(f, "warning"),
+            DiagnosticLevel::Note => write!(f, "note"),
+            DiagnosticLevel::Help => write!(f, "help"),
+            DiagnosticLevel::Other(level) => write!(f, "{}", level),
+        }
+    }
+}
+
+#[test]
+fn should_parse_compiler_messages() {
+    let output = r#"
+{"reason":"compiler-message","package_id":"faulty-ptx_crate 0.1.0 (path+file:///faulty-crate)","target":{"kind":["lib"],"crate_types":["lib"],"name":"faulty_ptx_crate","src_path":"/faulty-crate/src/lib.rs","edition":"2015"},"message":{"message":"cannot find function `external_fn` in this scope","code":{"code":"E0425","explanation":null},"level":"error","spans":[{"file_name":"src/lib.rs","byte_start":155,"byte_end":166,"line_start":6,"line_end":6,"column_start":20,"column_end":31,"is_primary":true,"text":[],"label":"not found in this scope","suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[],"rendered":"error[E0425]: cannot find function `external_fn` in this scope\n"}}
+{"reason":"compiler-message","package_id":"faulty-ptx_crate 0.1.0 (path+file:///faulty-crate)","target":{"kind":["lib"],"crate_types":["lib"],"name":"faulty_ptx_crate","src_path":"/faulty-crate/src/lib.rs","edition":"2015"},"message":{"message":"aborting due to previous error","code":null,"level":"error","spans":[],"children":[],"rendered":null}}
+{"reason":"build-finished","success":false}
+"#;
+
+    let messages = Messages::parse(output);
+
+    assert!(messages.has_errors());
+    assert_eq!(
+        messages.get_diagnostics(),
+        &[
+            Diagnostic {
+                level: DiagnosticLevel::Error,
+                code: Some(String::from("E0425")),
+                message: String::from("cannot find function `external_fn` in this scope"),
+                file: Some(PathBuf::from("src/lib.rs")),
+                line: Some(6),
+                column: Some(20),
+                rendered: Some(String::from(
+                    "error[E0425]: cannot find function `external_fn` in this scope\n"
+                )),
+            },
+            Diagnostic {
+                level: DiagnosticLevel::Error,
+                code: None,
+                message: String::from("aborting due to previous error"),
+                file: None,
+                line: None,
+                column: None,
+                rendered: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn should_ignore_non_diagnostic_output() {
+    let output = r#"
+   Compiling sample-ptx_crate v0.1.0
+{"reason":"build-script-executed","package_id":"sample-ptx_crate 0.1.0","linked_libs":[],"linked_paths":[],"cfgs":[],"env":[],"out_dir":"/out"}
+{"reason":"compiler-message","message":{"message":"unused variable: `x`","code":{"code":"unused_variables","explanation":null},"level":"warning","spans":[],"children":[],"rendered":null}}
+"#;
+
+    let messages = Messages::parse(output);
+
+    assert!(!messages.has_errors());
+    assert_eq!(messages.get_diagnostics().len(), 1);
+    assert_eq!(
+        messages.get_diagnostics()[0].to_string(),
+        "warning[unused_variables]: unused variable: `x`"
+    );
+}
+
+#[test]
+fn should_parse_compiler_artifacts() {
+    let output = r#"
+{"reason":"compiler-artifact","package_id":"core 0.0.0","target":{"kind":["lib"],"crate_types":["lib"],"name":"core","src_path":"/core/src/lib.rs","edition":"2018"},"profile":{"opt_level":"3","debuginfo":null,"debug_assertions":false,"overflow_checks":false,"test":false},"features":[],"filenames":["/target/nvptx64-nvidia-cuda/release/deps/libcore-6b6a47d2d8e3f5a2.rlib"],"executable":null,"fresh":true}
+{"reason":"compiler-artifact","package_id":"sample-ptx_crate 0.1.0 (path+file:///sample-crate)","target":{"kind":["cdylib"],"crate_types":["cdylib"],"name":"sample_ptx_crate","src_path":"/sample-crate/src/lib.rs","edition":"2018"},"profile":{"opt_level":"3","debuginfo":null,"debug_assertions":false,"overflow_checks":false,"test":false},"features":[],"filenames":["/target/nvptx64-nvidia-cuda/release/sample_ptx_crate.ptx"],"executable":null,"fresh":false}
+{"reason":"build-finished","success":true}
+"#;
+
+    let messages = Messages::parse(output);
+
+    assert!(!messages.has_errors());
+    assert_eq!(messages.get_artifacts().len(), 2);
+
+    let artifact = {
+        messages
+            .find_artifact(
+                Path::new("/sample-crate/src/lib.rs"),
+                "sample_ptx_crate",
+                "ptx",
+            )
+            .unwrap()
+    };
+
+    assert_eq!(artifact.kind, vec![String::from("cdylib")]);
+    assert!(!artifact.fresh);
+    assert_eq!(
+        artifact.find_file("ptx"),
+        Some(Path::new(
+            "/target/nvptx64-nvidia-cuda/release/sample_ptx_crate.ptx"
+        ))
+    );
+
+    assert_eq!(
+        messages.find_artifact(Path::new("/core/src/lib.rs"), "core", "ptx"),
+        None
+    );
+    assert_eq!(
+        messages.find_artifact(
+            Path::new("/other-crate/src/lib.rs"),
+            "sample_ptx_crate",
+            "ptx"
+        ),
+        None
+    );
+}
@@ -0,0 +1,810 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+use failure::ResultExt;
+use serde::Serialize;
+
+use crate::error::*;
+
+/// Model of PTX assembly module: directives, kernels, variables and functions.
+///
+/// # Usage
+/// ``` no_run
+/// use ptx_builder::prelude::*;
+/// # use ptx_builder::error::Result;
+///
+/// # fn main() -> Result<()> {
+/// if let BuildStatus::Success(output) = Builder::new(".")?.build()? {
+///     let module = output.get_module()?;
+///
+///     for kernel in &module.kernels {
+///         println!("{}: {} params", kernel.name, kernel.params.len());
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Module {
+    /// PTX ISA version, e.g. `6.0`.
+    pub version: Option<String>,
+
+    /// Target architecture and platform options, e.g. `sm_61`.
+    pub target: Vec<String>,
+
+    /// Address size in bits.
+    pub address_size: Option<u32>,
+
+    /// Kernels - `.entry` functions.
+    pub kernels: Vec<Kernel>,
+
+    /// `.global` and `.const` variables.
+    pub variables: Vec<Variable>,
+
+    /// Device functions - `.func` declarations and definitions.
+    pub functions: Vec<Function>,
+}
+
+/// Kernel - `.entry` function.
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub struct Kernel {
+    pub name: String,
+    pub linkage: Linkage,
+    pub params: Vec<Param>,
+
+    /// Size of `.local` memory (e.g. stack frame) in bytes.
+    pub local_memory: usize,
+}
+
+/// Device function - `.func` declaration or definition.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Function {
+    pub name: String,
+    pub linkage: Linkage,
+    pub params: Vec<Param>,
+
+    /// Return values.
+    pub returns: Vec<Param>,
+
+    /// `false` for prototypes without body.
+    pub is_defined: bool,
+
+    /// Size of `.local` memory (e.g. stack frame) in bytes.
+    pub local_memory: usize,
+}
+
+/// Parameter of a kernel or a function.
+#[derive(PartialEq, Clone, Debug, Serialize)]
+pub struct Param {
+    pub name: String,
+
+    /// Fundamental type, e.g. `.u64` or `.b8`.
+    #[serde(rename = "type")]
+    pub ty: String,
+
+    pub align: Option<u32>,
+
+    /// Number of elements for array parameters.
+    pub array_len: Option<usize>,
+}
+
+/// Module-level variable.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Variable {
+    pub name: String,
+    pub linkage: Linkage,
+    pub space: StateSpace,
+
+    /// Fundamental type, e.g. `.u32` or `.b8`.
+    pub ty: String,
+
+    pub align: Option<u32>,
+
+    /// Number of elements for array variables, `Some(0)` for unsized `.extern` arrays.
+    pub array_len: Option<usize>,
+}
+
+/// Visibility of a module symbol.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Linkage {
+    /// `.visible` - available to other modules.
+    Visible,
+
+    /// `.extern` - defined in other module.
+    Extern,
+
+    /// `.weak` - visible, but can be overridden.
+    Weak,
+
+    /// No linking directive - available only inside of the module.
+    Internal,
+}
+
+/// State space of a module-level variable.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum StateSpace {
+    Global,
+    Const,
+    Shared,
+}
+
+impl Module {
+    /// Parses PTX assembly source.
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut module = Module::default();
+
+        for statement in split_statements(&strip_comments(source)) {
+            module.add_statement(&statement)?;
+        }
+
+        Ok(module)
+    }
+
+    /// Reads and parses PTX assembly file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let source = read_to_string(path.as_ref()).with_context(|_| {
+            BuildErrorKind::InternalError(format!(
+                "Unable to read PTX assembly: {}",
+                path.as_ref().display()
+            ))
+        })?;
+
+        Self::parse(&source)
+    }
+
+    /// Returns kernel by its name.
+    pub fn get_kernel(&self, name: &str) -> Option<&Kernel> {
+        self.kernels.iter().find(|kernel| kernel.name == name)
+    }
+
+    /// Returns variable by its name.
+    pub fn get_variable(&self, name: &str) -> Option<&Variable> {
+        self.variables.iter().find(|variable| variable.name == name)
+    }
+
+    /// Returns device function by its name.
+    pub fn get_function(&self, name: &str) -> Option<&Function> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+
+    fn add_statement(&mut self, statement: &Statement) -> Result<()> {
+        let (linkage, tokens) = Linkage::split(&statement.header);
+
+        match tokens.first().map(String::as_str) {
+            Some(".version") => {
+                self.version = tokens.get(1).cloned();
+            }
+
+            Some(".target") => {
+                self.target = tokens[1..]
+                    .join(" ")
+                    .split(',')
+                    .map(|item| item.trim().to_string())
+                    .filter(|item| !item.is_empty())
+                    .collect();
+            }
+
+            Some(".address_size") => {
+                let size = tokens.get(1).and_then(|size| size.parse().ok());
+
+                match size {
+                    Some(size) => self.address_size = Some(size),
+                    None => {
+                        bail!(malformed(statement));
+                    }
+                }
+            }
+
+            Some(".entry") => {
+                let signature = {
+                    Signature::parse(&statement.header, ".entry")
+                        .ok_or_else(|| malformed(statement))?
+                };
+
+                self.kernels.push(Kernel {
+                    name: signature.name.to_string(),
+                    linkage,
+                    params: parse_params(signature.params).ok_or_else(|| malformed(statement))?,
+                    local_memory: statement.get_local_memory(),
+                });
+            }
+
+            Some(".func") => {
+                let signature = {
+                    Signature::parse(&statement.header, ".func")
+                        .ok_or_else(|| malformed(statement))?
+                };
+
+                self.functions.push(Function {
+                    name: signature.name.to_string(),
+                    linkage,
+                    params: parse_params(signature.params).ok_or_else(|| malformed(statement))?,
+                    returns: parse_params(signature.returns).ok_or_else(|| malformed(statement))?,
+                    is_defined: statement.body.is_some(),
+                    local_memory: statement.get_local_memory(),
+                });
+            }
+
+            Some(space @ ".global") | Some(space @ ".const") | Some(space @ ".shared") => {
+                let space = match space {
+                    ".global" => StateSpace::Global,
+                    ".const" => StateSpace::Const,
+                    _ => StateSpace::Shared,
+                };
+
+                let declaration = match statement.header.find('=') {
+                    Some(position) => &statement.header[..position],
+                    None => &statement.header[..],
+                };
+
+                let (_, tokens) = Linkage::split(declaration);
+                let param = parse_param(&tokens[1..]).ok_or_else(|| malformed(statement))?;
+
+                self.variables.push(Variable {
+                    name: param.name,
+                    linkage,
+                    space,
+                    ty: param.ty,
+                    align: param.align,
+                    array_len: param.array_len,
+                });
+            }
+
+            // Other directives, e.g. `.file` or `.section`, are not modeled.
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+impl Linkage {
+    fn split(header: &str) -> (Self, Vec<String>) {
+        let mut tokens: Vec<String> = header.split_whitespace().map(String::from).collect();
+
+        let linkage = match tokens.first().map(String::as_str) {
+            Some(".visible") => Linkage::Visible,
+            Some(".extern") => Linkage::Extern,
+            Some(".weak") => Linkage::Weak,
+            _ => return (Linkage::Internal, tokens),
+        };
+
+        tokens.remove(0);
+        (linkage, tokens)
+    }
+}
+
+const LINE_DIRECTIVES: &[&str] = &[".version", ".target", ".address_size", ".file"];
+
+/// Top-level PTX statement: a directive or a declaration with optional body.
+struct Statement {
+    header: String,
+    body: Option<String>,
+}
+
+fn malformed(statement: &Statement) -> Error {
+    Error::from(BuildErrorKind::InternalError(format!(
+        "Malformed PTX statement: '{}'",
+        statement.header
+    )))
+}
+
+fn strip_comments(source: &str) -> String {
+    let mut output = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+
+    while let Some(current) = chars.next() {
+        match (current, chars.peek()) {
+            // String literals, e.g. paths of `.file` directives, may contain `//` or `/*`.
+            ('"', _) => {
+                let mut escaped = false;
+
+                output.push(current);
+
+                for next in chars.by_ref() {
+                    output.push(next);
+
+                    match next {
+                        _ if escaped => escaped = false,
+                        '\\' => escaped = true,
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+
+            ('/', Some('/')) => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        output.push('\n');
+                        break;
+                    }
+                }
+            }
+
+            ('/', Some('*')) => {
+                chars.next();
+
+                let mut previous = ' ';
+
+                for next in chars.by_ref() {
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+
+                    previous = next;
+                }
+
+                output.push(' ');
+            }
+
+            _ => output.push(current),
+        }
+    }
+
+    output
+}
+
+/// Splits source into top-level statements, keeping function bodies apart from headers.
+fn split_statements(source: &str) -> Vec<Statement> {
+    let mut statements = Vec::new();
+    let mut header = String::new();
+    let mut body = String::new();
+    let mut depth = 0;
+    let mut initializer = false;
+
+    for current in source.chars() {
+        if depth > 0 {
+            match current {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+
+            if depth == 0 {
+                statements.push(Statement::new(&header, Some(&body)));
+                header.clear();
+                body.clear();
+            } else {
+                body.push(current);
+            }
+
+            continue;
+        }
+
+        match current {
+            '=' => {
+                initializer = true;
+                header.push(current);
+            }
+
+            // Braces of variable initializers are kept in the statement.
+            '{' if !initializer => depth += 1,
+
+            ';' => {
+                statements.push(Statement::new(&header, None));
+                header.clear();
+                initializer = false;
+            }
+
+            // Module directives are terminated by a line break instead of semicolon.
+            '\n' if is_line_directive(&header) => {
+                statements.push(Statement::new(&header, None));
+                header.clear();
+            }
+
+            _ => header.push(current),
+        }
+    }
+
+    statements.push(Statement::new(&header, None));
+    statements
+        .into_iter()
+        .filter(|statement| !statement.header.is_empty())
+        .collect()
+}
+
+fn is_line_directive(header: &str) -> bool {
+    let directive = header.split_whitespace().next().unwrap_or_default();
+
+    LINE_DIRECTIVES.contains(&directive)
+}
+
+impl Statement {
+    fn new(header: &str, body: Option<&str>) -> Self {
+        Statement {
+            header: header.split_whitespace().collect::<Vec<_>>().join(" "),
+            body: body.map(String::from),
+        }
+    }
+
+    /// Returns total size of `.local` declarations of the body, e.g. `__local_depot0[32]`.
+    fn get_local_memory(&self) -> usize {
+        let body = match self.body {
+            Some(ref body) => body,
+            None => return 0,
+        };
+
+        body.split(';')
+            .filter_map(|instruction| {
+                let instruction = instruction.rsplit(['{', '}']).next()?;
+                let tokens: Vec<String> =
+                    { instruction.split_whitespace().map(String::from).collect() };
+
+                match tokens.first().map(String::as_str) {
+                    Some(".local") => parse_param(&tokens[1..]),
+                    _ => None,
+                }
+            })
+            .map(|declaration| get_type_size(&declaration.ty) * declaration.array_len.unwrap_or(1))
+            .sum()
+    }
+}
+
+/// Declaration of `.entry` or `.func`: `keyword [(returns)] name [(params)]`.
+struct Signature<'a> {
+    name: &'a str,
+    returns: &'a str,
+    params: &'a str,
+}
+
+impl<'a> Signature<'a> {
+    fn parse(header: &'a str, keyword: &str) -> Option<Self> {
+        let rest = header[header.find(keyword)? + keyword.len()..].trim_start();
+        let (returns, rest) = Self::parse_group(rest)?;
+
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == '(')
+            .unwrap_or(rest.len());
+
+        if end == 0 {
+            return None;
+        }
+
+        // Performance tuning directives, e.g. `.maxntid`, can follow the params.
+        let (params, _) = Self::parse_group(rest[end..].trim_start())?;
+
+        Some(Signature {
+            name: &rest[..end],
+            returns,
+            params,
+        })
+    }
+
+    fn parse_group(source: &'a str) -> Option<(&'a str, &'a str)> {
+        if !source.starts_with('(') {
+            return Some(("", source));
+        }
+
+        let end = source.find(')')?;
+
+        Some((&source[1..end], source[end + 1..].trim_start()))
+    }
+}
+
+fn parse_params(group: &str) -> Option<Vec<Param>> {
+    group
+        .split(',')
+        .map(str::trim)
+        .filter(|param| !param.is_empty())
+        .map(|param| {
+            let tokens: Vec<String> = param.split_whitespace().map(String::from).collect();
+
+            match tokens.first().map(String::as_str) {
+                Some(".param") | Some(".reg") => parse_param(&tokens[1..]),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Parses `[.align N] [.ptr .space .align N] .type name[len]` declaration.
+fn parse_param(tokens: &[String]) -> Option<Param> {
+    let mut align = None;
+    let mut ty = None;
+    let mut iter = tokens.iter();
+
+    let declaration = tokens.last()?;
+
+    while let Some(token) = iter.next() {
+        match token.as_str() {
+            ".align" if ty.is_none() => {
+                align = Some(iter.next()?.parse().ok()?);
+            }
+
+            token if ty.is_none() && is_fundamental_type(token) => {
+                ty = Some(token.to_string());
+            }
+
+            _ => {}
+        }
+    }
+
+    let (name, array_len) = match declaration.find('[') {
+        Some(position) => {
+            let len = declaration[position + 1..].trim_end_matches(']');
+
+            (
+                declaration[..position].to_string(),
+                Some(if len.is_empty() { 0 } else { len.parse().ok()? }),
+            )
+        }
+
+        None => (declaration.to_string(), None),
+    };
+
+    if name.starts_with('.') {
+        return None;
+    }
+
+    Some(Param {
+        name,
+        ty: ty?,
+        align,
+        array_len,
+    })
+}
+
+/// Returns size of fundamental type in bytes.
+fn get_type_size(ty: &str) -> usize {
+    match ty {
+        ".e4m3" | ".e5m2" => 1,
+        ".bf16" | ".e4m3x2" | ".e5m2x2" => 2,
+        ".bf16x2" | ".f16x2" | ".tf32" => 4,
+
+        _ => {
+            let bits: usize = ty[2..].parse().unwrap_or(8);
+
+            (bits / 8).max(1)
+        }
+    }
+}
+
+/// Alternate floating point types, whose names don't follow `.{kind}{bits}` pattern.
+const ALTERNATE_FLOAT_TYPES: &[&str] = &[
+    ".bf16", ".bf16x2", ".f16x2", ".tf32", ".e4m3", ".e5m2", ".e4m3x2", ".e5m2x2",
+];
+
+fn is_fundamental_type(token: &str) -> bool {
+    if ALTERNATE_FLOAT_TYPES.contains(&token) {
+        return true;
+    }
+
+    let (prefix, size) = token.split_at(token.len().min(2));
+
+    match prefix {
+        ".s" | ".u" | ".f" | ".b" => !size.is_empty() && size.chars().all(|c| c.is_ascii_digit()),
+        _ => token == ".pred",
+    }
+}
+
+#[cfg(test)]
+const SAMPLE_PTX: &str = r#"
+//
+// Generated by LLVM NVPTX Back-End
+//
+
+.version 6.0
+.target sm_61
+.address_size 64
+
+	// .globl	the_kernel
+.global .align 4 .u32 counter;
+.visible .const .align 8 .b8 table[16] = {1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0};
+.extern .shared .align 16 .b8 scratch[];
+
+.func  (.param .b64 func_retval0) helper(
+	.param .b64 helper_param_0
+)
+;
+.extern .func abort
+()
+;
+/* block
+   comment */
+.visible .entry the_kernel(
+	.param .u64 the_kernel_param_0,
+	.param .u64 .ptr .global .align 1 the_kernel_param_1,
+	.param .align 8 .b8 the_kernel_param_2[16]
+)
+.maxntid 256, 1, 1
+{
+	.local .align 8 .b8 	__local_depot0[32];
+	.reg .b64 	%rd<4>;
+	{ // nested block
+	}
+	ret;
+}
+
+.func  (.param .b64 func_retval0) helper(
+	.param .b64 helper_param_0
+)
+{
+	ret;
+}
+"#;
+
+#[test]
+fn should_parse_module_directives() {
+    let module = Module::parse(SAMPLE_PTX).unwrap();
+
+    assert_eq!(module.version, Some(String::from("6.0")));
+    assert_eq!(module.target, vec![String::from("sm_61")]);
+    assert_eq!(module.address_size, Some(64));
+}
+
+#[test]
+fn should_parse_module_kernels() {
+    let module = Module::parse(SAMPLE_PTX).unwrap();
+
+    assert_eq!(module.kernels.len(), 1);
+    assert_eq!(
+        module.get_kernel("the_kernel"),
+        Some(&Kernel {
+            name: String::from("the_kernel"),
+            linkage: Linkage::Visible,
+            params: vec![
+                Param {
+                    name: String::from("the_kernel_param_0"),
+                    ty: String::from(".u64"),
+                    align: None,
+                    array_len: None,
+                },
+                Param {
+                    name: String::from("the_kernel_param_1"),
+                    ty: String::from(".u64"),
+                    align: None,
+                    array_len: None,
+                },
+                Param {
+                    name: String::from("the_kernel_param_2"),
+                    ty: String::from(".b8"),
+                    align: Some(8),
+                    array_len: Some(16),
+                },
+            ],
+            local_memory: 32,
+        })
+    );
+}
+
+#[test]
+fn should_parse_module_variables() {
+    let module = Module::parse(SAMPLE_PTX).unwrap();
+
+    assert_eq!(
+        module.variables,
+        vec![
+            Variable {
+                name: String::from("counter"),
+                linkage: Linkage::Internal,
+                space: StateSpace::Global,
+                ty: String::from(".u32"),
+                align: Some(4),
+                array_len: None,
+            },
+            Variable {
+                name: String::from("table"),
+                linkage: Linkage::Visible,
+                space: StateSpace::Const,
+                ty: String::from(".b8"),
+                align: Some(8),
+                array_len: Some(16),
+            },
+            Variable {
+                name: String::from("scratch"),
+                linkage: Linkage::Extern,
+                space: StateSpace::Shared,
+                ty: String::from(".b8"),
+                align: Some(16),
+                array_len: Some(0),
+            },
+        ]
+    );
+}
+
+#[test]
+fn should_parse_module_functions() {
+    let module = Module::parse(SAMPLE_PTX).unwrap();
+
+    assert_eq!(module.functions.len(), 3);
+
+    let helper_param = Param {
+        name: String::from("helper_param_0"),
+        ty: String::from(".b64"),
+        align: None,
+        array_len: None,
+    };
+
+    let helper_return = Param {
+        name: String::from("func_retval0"),
+        ty: String::from(".b64"),
+        align: None,
+        array_len: None,
+    };
+
+    assert_eq!(
+        module.functions[0],
+        Function {
+            name: String::from("helper"),
+            linkage: Linkage::Internal,
+            params: vec![helper_param.clone()],
+            returns: vec![helper_return.clone()],
+            is_defined: false,
+            local_memory: 0,
+        }
+    );
+
+    assert_eq!(
+        module.functions[1],
+        Function {
+            name: String::from("abort"),
+            linkage: Linkage::Extern,
+            params: vec![],
+            returns: vec![],
+            is_defined: false,
+            local_memory: 0,
+        }
+    );
+
+    assert_eq!(
+        module.functions[2],
+        Function {
+            name: String::from("helper"),
+            linkage: Linkage::Internal,
+            params: vec![helper_param],
+            returns: vec![helper_return],
+            is_defined: true,
+            local_memory: 0,
+        }
+    );
+}
+
+#[test]
+fn should_report_malformed_statements() {
+    match Module::parse(".address_size sixty-four")
+        .unwrap_err()
+        .kind()
+    {
+        BuildErrorKind::InternalError(message) => {
+            assert_eq!(
+                message,
+                "Malformed PTX statement: '.address_size sixty-four'"
+            );
+        }
+
+        _ => unreachable!("it should fail with proper error"),
+    }
+}
+
+#[test]
+fn should_parse_alternate_types_and_string_literals() {
+    let module = Module::parse(
+        r#"
+.version 7.8
+.target sm_90
+.address_size 64
+
+.file 1 "/home/user//kernels/src/lib.rs"
+.file 2 "/home/user/kernels/src/*/mod.rs"
+
+.visible .entry the_kernel(
+	.param .bf16 the_kernel_param_0,
+	.param .f16x2 the_kernel_param_1
+)
+{
+	.local .align 2 .b8 	__local_depot0[6];
+	.local .bf16 	scratch[5];
+	ret;
+}
+"#,
+    )
+    .unwrap();
+
+    let kernel = module.get_kernel("the_kernel").unwrap();
+
+    assert_eq!(kernel.params[0].ty, ".bf16");
+    assert_eq!(kernel.params[1].ty, ".f16x2");
+    assert_eq!(kernel.local_memory, 16);
+}
@@ -1,11 +1,15 @@
+use std::env;
 use std::fmt;
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::thread;
 
 use colored::*;
-use failure::Fail;
+use failure::{Fail, ResultExt};
 
-use crate::builder::{BuildStatus, Builder};
+use crate::builder::{BuildStatus, Builder, MultiBuildStatus};
 use crate::error::*;
+use crate::lint::Lint;
 
 /// Cargo integration adapter.
 ///
@@ -23,6 +27,9 @@ use crate::error::*;
 /// ```
 pub struct CargoAdapter {
     env_name: String,
+    bindings: bool,
+    nul_terminated: bool,
+    kernel_manifest: bool,
 }
 
 impl CargoAdapter {
@@ -38,16 +45,145 @@ impl CargoAdapter {
     pub fn with_env_var<S: AsRef<str>>(env_name: S) -> Self {
         CargoAdapter {
             env_name: env_name.as_ref().to_string(),
+            bindings: false,
+            nul_terminated: false,
+            kernel_manifest: false,
         }
     }
 
+    /// Generates host-side bindings of the assembly into `OUT_DIR`
+    /// (see [`BuildOutput::write_bindings`](../builder/struct.BuildOutput.html#method.write_bindings)).
+    ///
+    /// Path to the bindings is provided via additional environment variable,
+    /// e.g. `PTX_PATH_BINDINGS`, so the host crate can include them:
+    /// ```ignore
+    /// include!(env!("PTX_PATH_BINDINGS"));
+    /// ```
+    ///
+    /// Multi-architecture builds get bindings for every architecture,
+    /// e.g. `PTX_PATH_SM_35_BINDINGS` and `PTX_PATH_SM_61_BINDINGS`.
+    pub fn enable_bindings(mut self) -> Self {
+        self.bindings = true;
+        self
+    }
+
+    /// Writes NUL-terminated copy of the assembly
+    /// (see [`BuildOutput::write_nul_terminated_assembly`](../builder/struct.BuildOutput.html#method.write_nul_terminated_assembly)).
+    ///
+    /// Path to the copy is provided via additional environment variable,
+    /// e.g. `PTX_PATH_CSTR`, so the host crate can use it without allocations:
+    /// ```ignore
+    /// use std::ffi::CStr;
+    ///
+    /// let ptx = CStr::from_bytes_with_nul(include_bytes!(env!("PTX_PATH_CSTR")))?;
+    /// ```
+    pub fn enable_nul_terminated_assembly(mut self) -> Self {
+        self.nul_terminated = true;
+        self
+    }
+
+    /// Writes JSON manifest of the assembly kernels for tools outside of Rust
+    /// (see [`BuildOutput::write_kernel_manifest`](../builder/struct.BuildOutput.html#method.write_kernel_manifest)).
+    ///
+    /// Path to the manifest is provided via additional environment variable,
+    /// e.g. `PTX_PATH_KERNELS`.
+    pub fn enable_kernel_manifest(mut self) -> Self {
+        self.kernel_manifest = true;
+        self
+    }
+
     /// Runs build process and reports artifacts to Cargo.
     ///
+    /// Suspicious findings in the assembly are reported as Cargo warnings,
+    /// and so is the assembly, that the linter is unable to parse.
+    ///
     /// Depends on whether the build was successful or not, will either
     /// call `exit(0)` or `exit(1)` and print error log to `stderr`.
-    #[allow(clippy::needless_pass_by_value)]
     pub fn build(&self, builder: Builder) -> ! {
-        if let Err(error) = self.build_inner(&builder) {
+        Self::exit_with(self.try_build(&builder))
+    }
+
+    /// Runs build process and reports artifacts to Cargo, but unlike
+    /// [`build`](#method.build) returns control back to the caller.
+    ///
+    /// # Usage in `build.rs`
+    /// ```no_run
+    /// use ptx_builder::error::Result;
+    /// use ptx_builder::prelude::*;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let report = CargoAdapter::with_env_var("PTX_PATH").try_build(&Builder::new(".")?)?;
+    ///
+    ///     for path in report.get_outputs() {
+    ///         // e.g. generate bindings for the assembly...
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn try_build(&self, builder: &Builder) -> Result<BuildReport> {
+        let report = self.build_inner(builder)?;
+
+        report.print();
+        Ok(report)
+    }
+
+    /// Runs build process for every specified GPU architecture and reports artifacts to Cargo.
+    ///
+    /// Every assembly path is provided via its own environment variable,
+    /// named after the architecture, e.g. `PTX_PATH_SM_35` and `PTX_PATH_SM_61`,
+    /// and so are optional JSON manifests and bindings of their kernels,
+    /// e.g. `PTX_PATH_SM_35_KERNELS` and `PTX_PATH_SM_35_BINDINGS`.
+    /// Additionally, `PTX_PATH_MANIFEST` points to a TOML manifest of all the assemblies
+    /// (see [`MultiBuildOutput::write_manifest`](../builder/struct.MultiBuildOutput.html#method.write_manifest)),
+    /// which is written into `OUT_DIR`.
+    ///
+    /// Exits the same way as [`build`](#method.build) does.
+    ///
+    /// # Usage in `build.rs`
+    /// ```no_run
+    /// use ptx_builder::error::Result;
+    /// use ptx_builder::prelude::*;
+    ///
+    /// fn main() -> Result<()> {
+    ///     CargoAdapter::with_env_var("PTX_PATH")
+    ///         .build_for_target_cpus(Builder::new(".")?, ["sm_35", "sm_61"]);
+    /// }
+    /// ```
+    pub fn build_for_target_cpus<I, S>(&self, builder: Builder, target_cpus: I) -> !
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self::exit_with(self.try_build_for_target_cpus(&builder, target_cpus))
+    }
+
+    /// Performs the same as [`build_for_target_cpus`](#method.build_for_target_cpus),
+    /// but returns control back to the caller.
+    pub fn try_build_for_target_cpus<I, S>(
+        &self,
+        builder: &Builder,
+        target_cpus: I,
+    ) -> Result<BuildReport>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let target_cpus: Vec<String> = {
+            target_cpus
+                .into_iter()
+                .map(|target_cpu| target_cpu.as_ref().to_string())
+                .collect()
+        };
+
+        let report = self.build_for_target_cpus_inner(builder, &target_cpus)?;
+
+        report.print();
+        Ok(report)
+    }
+
+    fn exit_with(result: Result<BuildReport>) -> ! {
+        if let Err(error) = result {
             eprintln!("{}", ErrorLogPrinter::print(error));
             exit(1);
         } else {
@@ -55,28 +191,408 @@ impl CargoAdapter {
         }
     }
 
-    fn build_inner(&self, builder: &Builder) -> Result<()> {
+    fn build_inner(&self, builder: &Builder) -> Result<BuildReport> {
+        let mut report = BuildReport::default();
+        let bindings_env_name = format!("{}_BINDINGS", self.env_name);
+
         match builder.build()? {
             BuildStatus::Success(output) => {
-                let dependencies = output.dependencies()?;
+                report.add_env_var(&self.env_name, output.get_assembly_path());
+                report.outputs.push(output.get_assembly_path());
 
-                println!(
-                    "cargo:rustc-env={}={}",
-                    self.env_name,
-                    output.get_assembly_path().display()
-                );
+                if self.kernel_manifest {
+                    let kernel_manifest_path = output.write_kernel_manifest()?;
 
-                for path in dependencies {
-                    println!("cargo:rerun-if-changed={}", path.display());
+                    report.add_env_var(format!("{}_KERNELS", self.env_name), &kernel_manifest_path);
+                    report.outputs.push(kernel_manifest_path);
                 }
+
+                if self.nul_terminated {
+                    let nul_terminated_path = output.write_nul_terminated_assembly()?;
+
+                    report.add_env_var(format!("{}_CSTR", self.env_name), &nul_terminated_path);
+                    report.outputs.push(nul_terminated_path);
+                }
+
+                if self.bindings {
+                    let bindings_path = Self::get_out_dir_path(&self.env_name, "rs")?;
+
+                    output.write_bindings(&bindings_path)?;
+
+                    report.add_env_var(bindings_env_name, &bindings_path);
+                    report.outputs.push(bindings_path);
+                }
+
+                report.add_lints(output.lint());
+                report.dependencies = output.dependencies()?;
             }
 
             BuildStatus::NotNeeded => {
-                println!("cargo:rustc-env={}=/dev/null", self.env_name);
+                report.add_env_var(&self.env_name, "/dev/null");
+
+                if self.kernel_manifest {
+                    report.add_env_var(format!("{}_KERNELS", self.env_name), "/dev/null");
+                }
+
+                if self.nul_terminated {
+                    report.add_env_var(format!("{}_CSTR", self.env_name), "/dev/null");
+                }
+
+                if self.bindings {
+                    report.add_env_var(bindings_env_name, "/dev/null");
+                }
             }
         };
 
-        Ok(())
+        Ok(report)
+    }
+
+    fn build_for_target_cpus_inner(
+        &self,
+        builder: &Builder,
+        target_cpus: &[String],
+    ) -> Result<BuildReport> {
+        let mut report = BuildReport::default();
+        let manifest_env_name = format!("{}_MANIFEST", self.env_name);
+
+        match builder.build_for_target_cpus(target_cpus)? {
+            MultiBuildStatus::Success(output) => {
+                let manifest_path = Self::get_out_dir_path(&self.env_name, "toml")?;
+
+                output.write_manifest(&manifest_path)?;
+
+                for (target_cpu, cpu_output) in output.iter() {
+                    let env_name = self.get_target_cpu_env_name(target_cpu);
+
+                    report.add_env_var(&env_name, cpu_output.get_assembly_path());
+                    report.outputs.push(cpu_output.get_assembly_path());
+
+                    if self.kernel_manifest {
+                        let kernel_manifest_path = cpu_output.write_kernel_manifest()?;
+
+                        report.add_env_var(format!("{}_KERNELS", env_name), &kernel_manifest_path);
+                        report.outputs.push(kernel_manifest_path);
+                    }
+
+                    if self.nul_terminated {
+                        let nul_terminated_path = cpu_output.write_nul_terminated_assembly()?;
+
+                        report.add_env_var(format!("{}_CSTR", env_name), &nul_terminated_path);
+                        report.outputs.push(nul_terminated_path);
+                    }
+
+                    if self.bindings {
+                        let bindings_path = Self::get_out_dir_path(&env_name, "rs")?;
+
+                        cpu_output.write_bindings(&bindings_path)?;
+
+                        report.add_env_var(format!("{}_BINDINGS", env_name), &bindings_path);
+                        report.outputs.push(bindings_path);
+                    }
+
+                    report.add_lints(cpu_output.lint());
+                }
+
+                report.add_env_var(manifest_env_name, &manifest_path);
+                report.outputs.push(manifest_path);
+                report.dependencies = output.dependencies()?;
+            }
+
+            MultiBuildStatus::NotNeeded => {
+                for target_cpu in target_cpus {
+                    let env_name = self.get_target_cpu_env_name(target_cpu);
+
+                    report.add_env_var(&env_name, "/dev/null");
+
+                    if self.kernel_manifest {
+                        report.add_env_var(format!("{}_KERNELS", env_name), "/dev/null");
+                    }
+
+                    if self.nul_terminated {
+                        report.add_env_var(format!("{}_CSTR", env_name), "/dev/null");
+                    }
+
+                    if self.bindings {
+                        report.add_env_var(format!("{}_BINDINGS", env_name), "/dev/null");
+                    }
+                }
+
+                report.add_env_var(manifest_env_name, "/dev/null");
+            }
+        };
+
+        Ok(report)
+    }
+
+    fn get_target_cpu_env_name(&self, target_cpu: &str) -> String {
+        format!("{}_{}", self.env_name, target_cpu.to_uppercase())
+    }
+
+    fn get_out_dir_path(env_name: &str, extension: &str) -> Result<PathBuf> {
+        let out_dir = {
+            env::var("OUT_DIR").context("Unable to get `OUT_DIR`, is it a `build.rs` script?")?
+        };
+
+        Ok(PathBuf::from(out_dir).join(format!("{}.{}", env_name.to_lowercase(), extension)))
+    }
+}
+
+/// Builds several device crates and reports all their artifacts to Cargo at once.
+///
+/// Every crate provides its PTX assembly path via its own environment variable,
+/// the same way [`CargoAdapter`](struct.CargoAdapter.html) does.
+/// Nothing is reported to Cargo unless every crate was built successfully,
+/// otherwise errors of all the failed crates are printed together.
+///
+/// # Usage in `build.rs`
+/// ```no_run
+/// use ptx_builder::error::Result;
+/// use ptx_builder::prelude::*;
+///
+/// fn main() -> Result<()> {
+///     BuildSet::new()
+///         .add("MATH_PTX_PATH", Builder::new("math-kernels")?)
+///         .add("IMAGE_PTX_PATH", Builder::new("image-kernels")?)
+///         .enable_parallel_builds()
+///         .build();
+/// }
+/// ```
+#[derive(Default)]
+pub struct BuildSet {
+    entries: Vec<(CargoAdapter, Builder)>,
+    parallel: bool,
+}
+
+impl BuildSet {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a device crate `builder`, whose PTX assembly path is provided
+    /// via `env_name` environment variable.
+    pub fn add<S: AsRef<str>>(self, env_name: S, builder: Builder) -> Self {
+        self.add_with_adapter(CargoAdapter::with_env_var(env_name), builder)
+    }
+
+    /// Adds a device crate `builder`, whose artifacts are reported by configured `adapter`,
+    /// e.g. with bindings or NUL-terminated assembly enabled.
+    ///
+    /// # Usage in `build.rs`
+    /// ```no_run
+    /// use ptx_builder::error::Result;
+    /// use ptx_builder::prelude::*;
+    ///
+    /// fn main() -> Result<()> {
+    ///     BuildSet::new()
+    ///         .add_with_adapter(
+    ///             CargoAdapter::with_env_var("MATH_PTX_PATH").enable_bindings(),
+    ///             Builder::new("math-kernels")?,
+    ///         )
+    ///         .add("IMAGE_PTX_PATH", Builder::new("image-kernels")?)
+    ///         .build();
+    /// }
+    /// ```
+    pub fn add_with_adapter(mut self, adapter: CargoAdapter, builder: Builder) -> Self {
+        self.entries.push((adapter, builder));
+        self
+    }
+
+    /// Build the crates simultaneously, each in its own thread.
+    pub fn enable_parallel_builds(mut self) -> Self {
+        self.parallel = true;
+        self
+    }
+
+    /// Runs build process of every crate and reports artifacts to Cargo.
+    ///
+    /// Exits the same way as [`CargoAdapter::build`](struct.CargoAdapter.html#method.build) does.
+    pub fn build(self) -> ! {
+        match self.try_build() {
+            Ok(_) => exit(0),
+
+            Err(errors) => {
+                for error in errors {
+                    eprintln!("{}", ErrorLogPrinter::print(error));
+                }
+
+                exit(1);
+            }
+        }
+    }
+
+    /// Runs build process of every crate and reports artifacts to Cargo, but unlike
+    /// [`build`](#method.build) returns control back to the caller.
+    ///
+    /// Returns either the report of all the crates, or errors of every failed crate.
+    ///
+    /// # Usage in `build.rs`
+    /// ```no_run
+    /// use ptx_builder::prelude::*;
+    /// use ptx_builder::error::Result;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let result = {
+    ///         BuildSet::new()
+    ///             .add("MATH_PTX_PATH", Builder::new("math-kernels")?)
+    ///             .add("IMAGE_PTX_PATH", Builder::new("image-kernels")?)
+    ///             .try_build()
+    ///     };
+    ///
+    ///     if let Err(errors) = result {
+    ///         for error in errors {
+    ///             eprintln!("{}", ErrorLogPrinter::print(error));
+    ///         }
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn try_build(&self) -> std::result::Result<BuildReport, Vec<Error>> {
+        let mut report = BuildReport::default();
+        let mut errors = Vec::new();
+
+        for result in self.build_inner() {
+            match result {
+                Ok(crate_report) => report.extend(crate_report),
+                Err(error) => errors.push(error),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        report.print();
+        Ok(report)
+    }
+
+    fn build_inner(&self) -> Vec<Result<BuildReport>> {
+        let build = |(adapter, builder): &(CargoAdapter, Builder)| -> Result<BuildReport> {
+            let report = adapter.build_inner(builder).with_context(|_| {
+                BuildErrorKind::CrateBuildFailed(builder.get_crate_path().to_path_buf())
+            })?;
+
+            Ok(report)
+        };
+
+        if !self.parallel {
+            return self.entries.iter().map(build).collect();
+        }
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = {
+                self.entries
+                    .iter()
+                    .map(|entry| scope.spawn(move || build(entry)))
+                    .collect()
+            };
+
+            handles
+                .into_iter()
+                .map(|handle| match handle.join() {
+                    Ok(result) => result,
+
+                    Err(_) => Err(Error::from(BuildErrorKind::InternalError(String::from(
+                        "Build thread panicked",
+                    )))),
+                })
+                .collect()
+        })
+    }
+}
+
+/// Everything that was reported to Cargo.
+#[derive(Default, Debug)]
+pub struct BuildReport {
+    env_vars: Vec<(String, String)>,
+    dependencies: Vec<PathBuf>,
+    outputs: Vec<PathBuf>,
+    warnings: Vec<String>,
+}
+
+impl BuildReport {
+    /// Returns names and values of provided environment variables.
+    pub fn get_env_vars(&self) -> &[(String, String)] {
+        &self.env_vars
+    }
+
+    /// Returns paths that Cargo watches for changes.
+    pub fn get_dependencies(&self) -> &[PathBuf] {
+        &self.dependencies
+    }
+
+    /// Returns paths to produced files: PTX assemblies, manifests and bindings.
+    pub fn get_outputs(&self) -> &[PathBuf] {
+        &self.outputs
+    }
+
+    /// Returns warnings about the assemblies, e.g. findings of
+    /// [`BuildOutput::lint`](../builder/struct.BuildOutput.html#method.lint).
+    pub fn get_warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Returns lines, that inform Cargo about the build.
+    pub fn get_cargo_lines(&self) -> Vec<String> {
+        let env_lines = {
+            self.env_vars
+                .iter()
+                .map(|(name, value)| format!("cargo:rustc-env={}={}", name, value))
+        };
+
+        let dependency_lines = {
+            self.dependencies
+                .iter()
+                .map(|path| format!("cargo:rerun-if-changed={}", path.display()))
+        };
+
+        let warning_lines = {
+            self.warnings
+                .iter()
+                .map(|warning| format!("cargo:warning={}", warning))
+        };
+
+        env_lines
+            .chain(dependency_lines)
+            .chain(warning_lines)
+            .collect()
+    }
+
+    fn add_env_var<S: AsRef<str>, P: AsRef<Path>>(&mut self, name: S, value: P) {
+        self.env_vars.push((
+            name.as_ref().to_string(),
+            value.as_ref().display().to_string(),
+        ));
+    }
+
+    fn add_lints(&mut self, lints: Result<Vec<Lint>>) {
+        let warnings = match lints {
+            Ok(lints) => lints.iter().map(|lint| format!("[PTX] {}", lint)).collect(),
+
+            // Lints are only hints, so unsupported assembly syntax should not fail the build.
+            Err(error) => vec![format!("[PTX] Unable to lint the assembly: {}", error)],
+        };
+
+        for warning in warnings {
+            // Every GPU architecture of the same crate would otherwise repeat it.
+            if !self.warnings.contains(&warning) {
+                self.warnings.push(warning);
+            }
+        }
+    }
+
+    fn extend(&mut self, other: BuildReport) {
+        self.env_vars.extend(other.env_vars);
+        self.dependencies.extend(other.dependencies);
+        self.outputs.extend(other.outputs);
+        self.warnings.extend(other.warnings);
+    }
+
+    fn print(&self) {
+        for line in self.get_cargo_lines() {
+            println!("{}", line);
+        }
     }
 }
 
@@ -1,30 +1,55 @@
-use std::collections::hash_map::DefaultHasher;
 use std::env;
+use std::ffi::OsStr;
 use std::fs;
-use std::hash::{Hash, Hasher};
-use std::io::{BufReader, Read};
 use std::path::{Path, PathBuf};
 
 use failure::ResultExt;
-use toml;
+use serde::Deserialize;
 
 use crate::builder::CrateType;
+use crate::cache::CacheKey;
 use crate::error::*;
+use crate::executable::{Cargo, ExecutableRunner};
 
-#[derive(Hash, Clone, Debug)]
-pub enum FilePrefix {
-    Library(String),
-    Binary(String),
-    Mixed { lib: String, bin: String },
-}
-
-#[derive(Hash, Clone, Debug)]
+#[derive(Clone, Debug)]
 /// Information about CUDA crate.
 pub struct Crate {
     name: String,
+    package_id: String,
     path: PathBuf,
     output_file_prefix: String,
-    deps_file_prefix: FilePrefix,
+    targets: Vec<Target>,
+    toolchain: Option<String>,
+    workspace_root: Option<PathBuf>,
+}
+
+/// Crate target, as reported by `cargo metadata`.
+#[derive(Clone, Debug)]
+pub struct Target {
+    name: String,
+    crate_type: CrateType,
+    src_path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct RawMetadata {
+    packages: Vec<RawPackage>,
+    workspace_root: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct RawPackage {
+    id: String,
+    name: String,
+    manifest_path: PathBuf,
+    targets: Vec<RawTarget>,
+}
+
+#[derive(Deserialize)]
+struct RawTarget {
+    name: String,
+    kind: Vec<String>,
+    src_path: PathBuf,
 }
 
 impl Crate {
@@ -48,114 +73,304 @@ impl Crate {
             }
         }
 
-        let cargo_toml: toml::Value = {
-            let mut reader = BufReader::new(
-                fs::File::open(path.join("Cargo.toml")).context(BuildErrorKind::OtherError)?,
-            );
+        let (package, workspace_root) = Self::load_package(&path)?;
+
+        let targets: Vec<_> = {
+            package
+                .targets
+                .into_iter()
+                .filter_map(Target::from_raw)
+                .collect()
+        };
 
-            let mut contents = String::new();
+        if targets.is_empty() {
+            bail!(BuildErrorKind::InternalError(
+                "Unable to find neither library nor binary target".into()
+            ));
+        }
 
-            reader
-                .read_to_string(&mut contents)
-                .context(BuildErrorKind::OtherError)?;
+        let toolchain = Self::find_toolchain(&path)?;
 
-            toml::from_str(&contents).context(BuildErrorKind::OtherError)?
+        Ok(Crate {
+            output_file_prefix: package.name.replace("-", "_"),
+            name: package.name,
+            package_id: package.id,
+            path,
+            targets,
+            toolchain,
+            workspace_root,
+        })
+    }
+
+    /// Runs `cargo metadata` and finds the package of the crate at `path`.
+    ///
+    /// Workspace root is returned only for workspace members.
+    fn load_package(path: &Path) -> Result<(RawPackage, Option<PathBuf>)> {
+        let manifest_path = path.join("Cargo.toml");
+
+        // `cargo` is called outside of the crate on purpose:
+        // `rustup` should not try to install the crate's toolchain just to read its metadata.
+        let output = {
+            ExecutableRunner::new(Cargo)
+                .with_args(["metadata", "--format-version", "1", "--no-deps"])
+                .with_args([OsStr::new("--manifest-path"), manifest_path.as_os_str()])
+                .run()
+                .context("Unable to get crate metadata")?
         };
 
-        let cargo_toml_name = match cargo_toml["package"]["name"].as_str() {
-            Some(name) => name,
-            None => {
-                bail!(BuildErrorKind::InternalError(String::from(
-                    "Cannot get crate name"
-                )));
-            }
+        let metadata: RawMetadata = {
+            serde_json::from_str(&output.stdout).context(BuildErrorKind::InternalError(
+                String::from("Unable to parse crate metadata"),
+            ))?
         };
 
-        let is_library = path.join("src").join("lib.rs").exists();
-        let is_binary = path.join("src").join("main.rs").exists();
+        let manifest_path = manifest_path
+            .canonicalize()
+            .context(BuildErrorKind::OtherError)?;
+
+        let package = metadata.packages.into_iter().find(|package| {
+            package
+                .manifest_path
+                .canonicalize()
+                .map(|path| path == manifest_path)
+                .unwrap_or(false)
+        });
+
+        let workspace_root = {
+            metadata
+                .workspace_root
+                .canonicalize()
+                .context(BuildErrorKind::OtherError)?
+        };
 
-        let output_file_prefix = cargo_toml_name.replace("-", "_");
+        let workspace_root = match manifest_path.parent() {
+            Some(crate_root) if crate_root == workspace_root => None,
+            _ => Some(workspace_root),
+        };
 
-        let deps_file_prefix = match (is_binary, is_library) {
-            (false, true) => FilePrefix::Library(format!("lib{}", output_file_prefix)),
-            (true, false) => FilePrefix::Binary(cargo_toml_name.to_string()),
+        match package {
+            Some(package) => Ok((package, workspace_root)),
 
-            (true, true) => FilePrefix::Mixed {
-                lib: format!("lib{}", output_file_prefix),
-                bin: cargo_toml_name.to_string(),
-            },
+            None => Err(Error::from(BuildErrorKind::InternalError(String::from(
+                "Cannot find crate package in metadata",
+            )))),
+        }
+    }
 
-            (false, false) => {
-                bail!(BuildErrorKind::InternalError(
-                    "Unable to find neither `lib.rs` nor `main.rs`".into()
-                ));
+    /// Looks for `rust-toolchain` or `rust-toolchain.toml` file the same way `rustup` does:
+    /// in the crate directory and its parents.
+    fn find_toolchain(path: &Path) -> Result<Option<String>> {
+        for directory in path.ancestors() {
+            for name in &["rust-toolchain", "rust-toolchain.toml"] {
+                let toolchain_file = directory.join(name);
+
+                if !toolchain_file.is_file() {
+                    continue;
+                }
+
+                let contents = {
+                    fs::read_to_string(&toolchain_file).with_context(|_| {
+                        BuildErrorKind::InternalError(format!(
+                            "Unable to read toolchain file: {}",
+                            toolchain_file.display()
+                        ))
+                    })?
+                };
+
+                return Ok(parse_toolchain_file(&contents));
             }
-        };
+        }
 
-        Ok(Crate {
-            name: cargo_toml_name.to_string(),
-            path,
-            output_file_prefix,
-            deps_file_prefix,
-        })
+        Ok(None)
     }
 
-    /// Returns PTX assmbly filename prefix.
-    pub fn get_output_file_prefix(&self) -> &str {
-        &self.output_file_prefix
-    }
+    /// Returns crate type that needs to be built, validating the requested one.
+    ///
+    /// Examples are never chosen implicitly.
+    pub fn get_crate_type(&self, crate_type: Option<CrateType>) -> Result<CrateType> {
+        let has_library = self.find_target(CrateType::Library).is_some();
+        let has_binary = self.find_target(CrateType::Binary).is_some();
+        let has_example = self.find_target(CrateType::Example).is_some();
 
-    /// Returns deps file filename prefix.
-    pub fn get_deps_file_prefix(&self, crate_type: Option<CrateType>) -> Result<String> {
-        match (&self.deps_file_prefix, crate_type) {
-            (FilePrefix::Library(prefix), Some(CrateType::Library)) => Ok(prefix.clone()),
-            (FilePrefix::Library(prefix), None) => Ok(prefix.clone()),
+        match (has_library, has_binary, crate_type) {
+            (true, _, Some(CrateType::Library)) => Ok(CrateType::Library),
+            (_, true, Some(CrateType::Binary)) => Ok(CrateType::Binary),
+            (_, _, Some(CrateType::Example)) if has_example => Ok(CrateType::Example),
 
-            (FilePrefix::Binary(prefix), Some(CrateType::Binary)) => Ok(prefix.clone()),
-            (FilePrefix::Binary(prefix), None) => Ok(prefix.clone()),
+            (true, false, None) => Ok(CrateType::Library),
+            (false, true, None) => Ok(CrateType::Binary),
 
-            (FilePrefix::Mixed { bin, .. }, Some(CrateType::Binary)) => Ok(bin.clone()),
-            (FilePrefix::Mixed { lib, .. }, Some(CrateType::Library)) => Ok(lib.clone()),
-            (FilePrefix::Mixed { .. }, None) => {
+            (true, true, None) => {
                 bail!(BuildErrorKind::MissingCrateType);
             }
 
-            (FilePrefix::Library(_), Some(CrateType::Binary)) => {
-                bail!(BuildErrorKind::InvalidCrateType("Binary".into()));
+            (false, false, None) => {
+                bail!(BuildErrorKind::InternalError(
+                    "Unable to find neither library nor binary target".into()
+                ));
+            }
+
+            (_, _, Some(crate_type)) => {
+                bail!(BuildErrorKind::InvalidCrateType(crate_type.to_string()));
             }
+        }
+    }
+
+    /// Returns the target that needs to be built, validating the requested crate type.
+    ///
+    /// When `name` is not specified, the binary named after the package is preferred.
+    pub fn get_target(&self, crate_type: Option<CrateType>, name: Option<&str>) -> Result<&Target> {
+        let crate_type = match (crate_type, name) {
+            (None, Some(name)) => match self.find_named_target(name) {
+                Some(target) => target.crate_type,
+                None => {
+                    bail!(BuildErrorKind::MissingCrateTarget(name.into()));
+                }
+            },
+
+            (crate_type, _) => self.get_crate_type(crate_type)?,
+        };
+
+        let mut targets = {
+            self.targets
+                .iter()
+                .filter(|target| target.crate_type == crate_type)
+        };
+
+        match name {
+            Some(name) => match targets.find(|target| target.name == name) {
+                Some(target) => Ok(target),
+                None => {
+                    bail!(BuildErrorKind::MissingCrateTarget(name.into()));
+                }
+            },
 
-            (FilePrefix::Binary(_), Some(CrateType::Library)) => {
-                bail!(BuildErrorKind::InvalidCrateType("Library".into()));
+            None => {
+                let target = {
+                    targets
+                        .find(|target| target.name == self.name)
+                        .or_else(|| self.find_target(crate_type))
+                };
+
+                Ok(target.expect("crate type is already validated"))
             }
         }
     }
 
+    fn find_target(&self, crate_type: CrateType) -> Option<&Target> {
+        self.targets
+            .iter()
+            .find(|target| target.crate_type == crate_type)
+    }
+
+    fn find_named_target(&self, name: &str) -> Option<&Target> {
+        self.targets.iter().find(|target| target.name == name)
+    }
+
     /// Returns crate name.
     pub fn get_name(&self) -> &str {
         &self.name
     }
 
+    /// Returns Cargo package id.
+    pub fn get_package_id(&self) -> &str {
+        &self.package_id
+    }
+
+    /// Returns toolchain name, specified in the crate's `rust-toolchain` file.
+    pub fn get_toolchain(&self) -> Option<&str> {
+        self.toolchain.as_deref()
+    }
+
     /// Returns crate root path.
     pub fn get_path(&self) -> &Path {
-        &self.path.as_path()
+        self.path.as_path()
     }
 
-    /// Returns temporary crate build location.
-    pub fn get_output_path(&self) -> Result<PathBuf> {
-        let mut path = env::temp_dir().join("ptx-builder-0.5");
+    /// Returns workspace root path, when the crate is a workspace member.
+    pub fn get_workspace_root(&self) -> Option<&Path> {
+        self.workspace_root.as_deref()
+    }
+
+    /// Returns path to `Cargo.lock`, which is shared between workspace members.
+    pub fn get_lockfile_path(&self) -> PathBuf {
+        self.get_workspace_root()
+            .unwrap_or_else(|| self.get_path())
+            .join("Cargo.lock")
+    }
+
+    /// Returns crate build location inside of `root`.
+    ///
+    /// Every distinct `cache_key` (e.g. target CPU) gets its own location,
+    /// so artifacts of different build configurations never get mixed.
+    pub fn get_output_path(&self, root: &Path, cache_key: &CacheKey) -> Result<PathBuf> {
+        let mut path = root.to_path_buf();
 
         path.push(&self.output_file_prefix);
-        path.push(format!("{:x}", self.get_hash()));
+        path.push(cache_key.to_string());
 
         fs::create_dir_all(&path).context(BuildErrorKind::OtherError)?;
         Ok(path)
     }
+}
+
+impl Target {
+    fn from_raw(raw: RawTarget) -> Option<Self> {
+        let is_library = raw.kind.iter().any(|kind| {
+            matches!(
+                kind.as_str(),
+                "lib" | "rlib" | "dylib" | "cdylib" | "staticlib"
+            )
+        });
+
+        let is_binary = raw.kind.iter().any(|kind| kind == "bin");
+        let is_example = raw.kind.iter().any(|kind| kind == "example");
+
+        let crate_type = match (is_library, is_binary, is_example) {
+            (true, _, _) => CrateType::Library,
+            (false, true, _) => CrateType::Binary,
+            (false, false, true) => CrateType::Example,
+
+            // Tests, benches and build scripts are not built.
+            (false, false, false) => return None,
+        };
+
+        Some(Target {
+            name: raw.name,
+            crate_type,
+            src_path: raw.src_path,
+        })
+    }
+
+    /// Returns target name, as Cargo knows it.
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
 
-    fn get_hash(&self) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        self.hash(&mut hasher);
+    /// Returns path to the target root source file, e.g. `src/lib.rs`.
+    pub fn get_src_path(&self) -> &Path {
+        &self.src_path
+    }
 
-        hasher.finish()
+    /// Returns target crate type.
+    pub fn get_crate_type(&self) -> CrateType {
+        self.crate_type
+    }
+}
+
+/// Parses both legacy (only toolchain name) and TOML toolchain file formats.
+fn parse_toolchain_file(contents: &str) -> Option<String> {
+    if let Ok(toml::Value::Table(table)) = toml::from_str(contents) {
+        return table
+            .get("toolchain")
+            .and_then(|toolchain| toolchain.get("channel"))
+            .and_then(toml::Value::as_str)
+            .map(String::from);
+    }
+
+    match contents.trim() {
+        "" => None,
+        name => Some(name.to_string()),
     }
 }
 
@@ -163,22 +378,15 @@ impl Crate {
 fn should_find_crate_names() {
     let source = Crate::analyse("tests/fixtures/sample-crate").unwrap();
 
-    assert_eq!(source.get_output_file_prefix(), "sample_ptx_crate");
+    assert_eq!(source.get_crate_type(None).unwrap(), CrateType::Library);
 
     assert_eq!(
-        source.get_deps_file_prefix(None).unwrap(),
-        "libsample_ptx_crate"
-    );
-
-    assert_eq!(
-        source
-            .get_deps_file_prefix(Some(CrateType::Library))
-            .unwrap(),
-        "libsample_ptx_crate"
+        source.get_crate_type(Some(CrateType::Library)).unwrap(),
+        CrateType::Library
     );
 
     match source
-        .get_deps_file_prefix(Some(CrateType::Binary))
+        .get_crate_type(Some(CrateType::Binary))
         .unwrap_err()
         .kind()
     {
@@ -194,22 +402,15 @@ fn should_find_crate_names() {
 fn should_find_app_crate_names() {
     let source = Crate::analyse("tests/fixtures/app-crate").unwrap();
 
-    assert_eq!(source.get_output_file_prefix(), "sample_app_ptx_crate");
-
-    assert_eq!(
-        source.get_deps_file_prefix(None).unwrap(),
-        "sample-app-ptx_crate"
-    );
+    assert_eq!(source.get_crate_type(None).unwrap(), CrateType::Binary);
 
     assert_eq!(
-        source
-            .get_deps_file_prefix(Some(CrateType::Binary))
-            .unwrap(),
-        "sample-app-ptx_crate"
+        source.get_crate_type(Some(CrateType::Binary)).unwrap(),
+        CrateType::Binary
     );
 
     match source
-        .get_deps_file_prefix(Some(CrateType::Library))
+        .get_crate_type(Some(CrateType::Library))
         .unwrap_err()
         .kind()
     {
@@ -225,28 +426,132 @@ fn should_find_app_crate_names() {
 fn should_find_mixed_crate_names() {
     let source = Crate::analyse("tests/fixtures/mixed-crate").unwrap();
 
-    assert_eq!(source.get_output_file_prefix(), "mixed_crate");
+    assert_eq!(
+        source.get_crate_type(Some(CrateType::Binary)).unwrap(),
+        CrateType::Binary
+    );
+
+    assert_eq!(
+        source.get_crate_type(Some(CrateType::Library)).unwrap(),
+        CrateType::Library
+    );
+
+    match source.get_crate_type(None).unwrap_err().kind() {
+        BuildErrorKind::MissingCrateType => {}
+        _ => unreachable!("it should fail with proper error"),
+    }
+}
+
+#[test]
+fn should_find_custom_layout_crate_targets() {
+    let source = Crate::analyse("tests/fixtures/custom-layout-crate").unwrap();
+
+    assert!(source.get_package_id().contains("custom-layout-crate"));
 
     assert_eq!(
         source
-            .get_deps_file_prefix(Some(CrateType::Binary))
-            .unwrap(),
-        "mixed-crate"
+            .get_target(Some(CrateType::Library), None)
+            .unwrap()
+            .get_name(),
+        "kernels"
     );
 
+    assert!(source
+        .get_target(Some(CrateType::Library), None)
+        .unwrap()
+        .get_src_path()
+        .ends_with("kernels/lib.rs"));
+
     assert_eq!(
         source
-            .get_deps_file_prefix(Some(CrateType::Library))
-            .unwrap(),
-        "libmixed_crate"
+            .get_target(Some(CrateType::Binary), None)
+            .unwrap()
+            .get_name(),
+        "launcher"
     );
 
-    match source.get_deps_file_prefix(None).unwrap_err().kind() {
+    match source.get_target(None, None).unwrap_err().kind() {
         BuildErrorKind::MissingCrateType => {}
         _ => unreachable!("it should fail with proper error"),
     }
 }
 
+#[test]
+fn should_find_named_crate_targets() {
+    let source = Crate::analyse("tests/fixtures/custom-layout-crate").unwrap();
+
+    let target = {
+        source
+            .get_target(Some(CrateType::Binary), Some("reduction"))
+            .unwrap()
+    };
+
+    assert_eq!(target.get_name(), "reduction");
+    assert_eq!(target.get_crate_type(), CrateType::Binary);
+
+    let target = source.get_target(None, Some("launcher")).unwrap();
+
+    assert_eq!(target.get_name(), "launcher");
+    assert_eq!(target.get_crate_type(), CrateType::Binary);
+
+    let target = source.get_target(Some(CrateType::Example), None).unwrap();
+
+    assert_eq!(target.get_name(), "scale");
+    assert_eq!(target.get_crate_type(), CrateType::Example);
+
+    match source
+        .get_target(Some(CrateType::Binary), Some("scale"))
+        .unwrap_err()
+        .kind()
+    {
+        BuildErrorKind::MissingCrateTarget(name) => {
+            assert_eq!(name, "scale");
+        }
+
+        _ => unreachable!("it should fail with proper error"),
+    }
+
+    match Crate::analyse("tests/fixtures/sample-crate")
+        .unwrap()
+        .get_target(Some(CrateType::Example), None)
+        .unwrap_err()
+        .kind()
+    {
+        BuildErrorKind::InvalidCrateType(kind) => {
+            assert_eq!(kind, "Example");
+        }
+
+        _ => unreachable!("it should fail with proper error"),
+    }
+}
+
+#[test]
+fn should_find_workspace_member_crate() {
+    let source = Crate::analyse("tests/fixtures/workspace-crate/kernels").unwrap();
+
+    assert_eq!(source.get_name(), "workspace-kernels");
+    assert_eq!(
+        source.get_target(None, None).unwrap().get_name(),
+        "workspace_kernels"
+    );
+
+    assert!(source
+        .get_workspace_root()
+        .unwrap()
+        .ends_with("tests/fixtures/workspace-crate"));
+
+    assert!(source
+        .get_lockfile_path()
+        .ends_with("tests/fixtures/workspace-crate/Cargo.lock"));
+
+    let source = Crate::analyse("tests/fixtures/sample-crate").unwrap();
+
+    assert_eq!(source.get_workspace_root(), None);
+    assert!(source
+        .get_lockfile_path()
+        .ends_with("tests/fixtures/sample-crate/Cargo.lock"));
+}
+
 #[test]
 fn should_check_existence_of_crate_path() {
     let result = Crate::analyse("tests/fixtures/non-existing-crate");
@@ -276,10 +581,75 @@ fn should_check_validity_of_crate_path() {
 #[test]
 fn should_provide_output_path() {
     let source_crate = Crate::analyse("tests/fixtures/sample-crate").unwrap();
+    let root = env::temp_dir().join("ptx-builder-0.5");
+
+    assert!(source_crate
+        .get_output_path(&root, &CacheKey::new())
+        .unwrap()
+        .starts_with(root.join("sample_ptx_crate")));
+}
+
+#[test]
+fn should_provide_distinct_output_paths_per_build_key() {
+    let source_crate = Crate::analyse("tests/fixtures/sample-crate").unwrap();
 
-    assert!(source_crate.get_output_path().unwrap().starts_with(
-        env::temp_dir()
-            .join("ptx-builder-0.5")
-            .join("sample_ptx_crate")
-    ));
+    assert_ne!(
+        source_crate
+            .get_output_path(
+                &env::temp_dir(),
+                &CacheKey::new().with("target-cpu", "sm_35")
+            )
+            .unwrap(),
+        source_crate
+            .get_output_path(
+                &env::temp_dir(),
+                &CacheKey::new().with("target-cpu", "sm_61")
+            )
+            .unwrap()
+    );
+
+    assert_eq!(
+        source_crate
+            .get_output_path(
+                &env::temp_dir(),
+                &CacheKey::new().with("target-cpu", "sm_61")
+            )
+            .unwrap(),
+        source_crate
+            .get_output_path(
+                &env::temp_dir(),
+                &CacheKey::new().with("target-cpu", "sm_61")
+            )
+            .unwrap()
+    );
+}
+
+#[test]
+fn should_parse_toolchain_files() {
+    assert_eq!(
+        parse_toolchain_file("nightly-2019-12-01\n"),
+        Some(String::from("nightly-2019-12-01"))
+    );
+
+    assert_eq!(
+        parse_toolchain_file(
+            "[toolchain]\nchannel = \"nightly-2019-12-01\"\ntargets = [\"nvptx64-nvidia-cuda\"]\n"
+        ),
+        Some(String::from("nightly-2019-12-01"))
+    );
+
+    assert_eq!(
+        parse_toolchain_file("[toolchain]\nprofile = \"minimal\"\n"),
+        None
+    );
+    assert_eq!(parse_toolchain_file("\n"), None);
+}
+
+#[test]
+fn should_find_crate_toolchain() {
+    let source = Crate::analyse("tests/fixtures/toolchain-crate").unwrap();
+    assert_eq!(source.get_toolchain(), Some("nightly-2019-12-01"));
+
+    let source = Crate::analyse("tests/fixtures/sample-crate").unwrap();
+    assert_eq!(source.get_toolchain(), None);
 }
@@ -1,13 +1,15 @@
+use std::collections::HashMap;
 use std::env;
 use std::env::current_dir;
-use std::fs::{remove_dir_all, File};
+use std::fs::{create_dir_all, read_to_string, remove_dir_all, File};
 use std::io::prelude::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use antidote::Mutex;
 use lazy_static::*;
 
 use ptx_builder::error::*;
+use ptx_builder::messages::DiagnosticLevel;
 use ptx_builder::prelude::*;
 
 lazy_static! {
@@ -43,19 +45,14 @@ fn should_write_assembly() {
 
     match builder.disable_colors().build().unwrap() {
         BuildStatus::Success(output) => {
-            let mut assembly_contents = String::new();
-
-            File::open(output.get_assembly_path())
-                .unwrap()
-                .read_to_string(&mut assembly_contents)
-                .unwrap();
+            let module = output.get_module().unwrap();
 
             assert!(output
                 .get_assembly_path()
                 .to_string_lossy()
                 .contains("release"));
 
-            assert!(assembly_contents.contains(".visible .entry the_kernel("));
+            assert!(module.get_kernel("the_kernel").is_some());
         }
 
         BuildStatus::NotNeeded => unreachable!(),
@@ -63,27 +60,144 @@ fn should_write_assembly() {
 }
 
 #[test]
-fn should_build_application_crate() {
+fn should_write_kernel_manifest() {
     cleanup_temp_location();
 
     let _lock = ENV_MUTEX.lock();
-    let builder = Builder::new("tests/fixtures/app-crate").unwrap();
+    let builder = Builder::new("tests/fixtures/sample-crate").unwrap();
+
+    match builder
+        .set_target_cpu("sm_61")
+        .disable_colors()
+        .build()
+        .unwrap()
+    {
+        BuildStatus::Success(output) => {
+            let manifest_path = output.write_kernel_manifest().unwrap();
+            let mut manifest_contents = String::new();
+
+            assert_eq!(
+                manifest_path,
+                output.get_assembly_path().with_extension("json")
+            );
+
+            File::open(manifest_path)
+                .unwrap()
+                .read_to_string(&mut manifest_contents)
+                .unwrap();
+
+            let manifest: serde_json::Value = serde_json::from_str(&manifest_contents).unwrap();
+
+            assert_eq!(manifest["target"], "sample_ptx_crate");
+            assert_eq!(manifest["target_cpu"], "sm_61");
+            assert_eq!(manifest["profile"], "release");
+            assert_eq!(manifest["ptx"]["target"][0], "sm_61");
+            assert_eq!(manifest["kernels"][0]["name"], "the_kernel");
+            assert_eq!(manifest["kernels"][0]["params"][0]["type"], ".u64");
+            assert!(manifest["kernels"][0]["local_memory"].is_u64());
+        }
+
+        BuildStatus::NotNeeded => unreachable!(),
+    }
+}
+
+#[test]
+fn should_write_nul_terminated_assembly() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    let builder = Builder::new("tests/fixtures/sample-crate").unwrap();
 
     match builder.disable_colors().build().unwrap() {
         BuildStatus::Success(output) => {
-            let mut assembly_contents = String::new();
+            let nul_terminated_path = output.write_nul_terminated_assembly().unwrap();
+            let mut assembly_contents = Vec::new();
+            let mut nul_terminated_contents = Vec::new();
+
+            assert_eq!(
+                nul_terminated_path,
+                output.get_assembly_path().with_extension("nul.ptx")
+            );
 
             File::open(output.get_assembly_path())
                 .unwrap()
-                .read_to_string(&mut assembly_contents)
+                .read_to_end(&mut assembly_contents)
                 .unwrap();
 
+            File::open(nul_terminated_path)
+                .unwrap()
+                .read_to_end(&mut nul_terminated_contents)
+                .unwrap();
+
+            assert_eq!(nul_terminated_contents.pop(), Some(0));
+            assert_eq!(nul_terminated_contents, assembly_contents);
+        }
+
+        BuildStatus::NotNeeded => unreachable!(),
+    }
+}
+
+#[test]
+fn should_check_expected_kernels() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    let builder = {
+        Builder::new("tests/fixtures/sample-crate")
+            .unwrap()
+            .add_expected_kernel("the_kernel")
+            .disable_colors()
+    };
+
+    match builder.build().unwrap() {
+        BuildStatus::Success(output) => {
+            assert!(output
+                .get_module()
+                .unwrap()
+                .get_kernel("the_kernel")
+                .is_some());
+        }
+
+        BuildStatus::NotNeeded => unreachable!(),
+    }
+
+    let builder = {
+        Builder::new("tests/fixtures/sample-crate")
+            .unwrap()
+            .add_expected_kernel("the_kernels")
+            .disable_colors()
+    };
+
+    match builder.build().unwrap_err().kind() {
+        BuildErrorKind::KernelsMismatch {
+            missing,
+            unexpected,
+        } => {
+            assert_eq!(missing, vec![String::from("the_kernels")]);
+            assert_eq!(unexpected, vec![String::from("the_kernel")]);
+        }
+
+        _ => unreachable!("it should fail with proper error"),
+    }
+}
+
+#[test]
+fn should_build_application_crate() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    let builder = Builder::new("tests/fixtures/app-crate").unwrap();
+
+    match builder.disable_colors().build().unwrap() {
+        BuildStatus::Success(output) => {
+            let module = output.get_module().unwrap();
+
             assert!(output
                 .get_assembly_path()
                 .to_string_lossy()
                 .contains("release"));
 
-            assert!(assembly_contents.contains(".visible .entry the_kernel("));
+            assert!(module.get_kernel("the_kernel").is_some());
         }
 
         BuildStatus::NotNeeded => unreachable!(),
@@ -160,6 +274,213 @@ fn should_build_mixed_crate_bin() {
     }
 }
 
+#[test]
+fn should_build_custom_layout_crate() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    let builder = Builder::new("tests/fixtures/custom-layout-crate").unwrap();
+
+    match builder
+        .set_crate_type(CrateType::Library)
+        .disable_colors()
+        .build()
+        .unwrap()
+    {
+        BuildStatus::Success(output) => {
+            let mut assembly_contents = String::new();
+
+            File::open(output.get_assembly_path())
+                .unwrap()
+                .read_to_string(&mut assembly_contents)
+                .unwrap();
+
+            assert!(output.get_assembly_path().ends_with("kernels.ptx"));
+            assert!(assembly_contents.contains(".visible .entry the_kernel("));
+        }
+
+        BuildStatus::NotNeeded => unreachable!(),
+    }
+}
+
+#[test]
+fn should_build_named_binary_target() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    let builder = Builder::new("tests/fixtures/custom-layout-crate").unwrap();
+
+    match builder
+        .set_target_name("reduction")
+        .disable_colors()
+        .build()
+        .unwrap()
+    {
+        BuildStatus::Success(output) => {
+            let mut assembly_contents = String::new();
+
+            File::open(output.get_assembly_path())
+                .unwrap()
+                .read_to_string(&mut assembly_contents)
+                .unwrap();
+
+            assert!(output.get_assembly_path().ends_with("reduction.ptx"));
+            assert!(assembly_contents.contains(".visible .entry the_reduction_kernel("));
+            assert!(!output.dependencies().unwrap().is_empty());
+        }
+
+        BuildStatus::NotNeeded => unreachable!(),
+    }
+}
+
+#[test]
+fn should_build_workspace_member_crate() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    let builder = Builder::new("tests/fixtures/workspace-crate/kernels").unwrap();
+
+    match builder.disable_colors().build().unwrap() {
+        BuildStatus::Success(output) => {
+            let mut assembly_contents = String::new();
+
+            File::open(output.get_assembly_path())
+                .unwrap()
+                .read_to_string(&mut assembly_contents)
+                .unwrap();
+
+            assert!(assembly_contents.contains(".visible .entry the_kernel("));
+
+            let workspace_root = {
+                env::current_dir()
+                    .unwrap()
+                    .join("tests/fixtures/workspace-crate")
+                    .canonicalize()
+                    .unwrap()
+            };
+
+            let dependencies = output.dependencies().unwrap();
+
+            assert!(dependencies.contains(&workspace_root.join("Cargo.toml")));
+            assert!(dependencies.contains(&workspace_root.join("Cargo.lock")));
+            assert!(!dependencies.contains(&workspace_root.join("kernels/Cargo.lock")));
+        }
+
+        BuildStatus::NotNeeded => unreachable!(),
+    }
+}
+
+#[test]
+fn should_write_assembly_into_out_dir() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    let out_dir = env::temp_dir().join("ptx-builder-out-dir");
+    remove_dir_all(&out_dir).unwrap_or_default();
+
+    let builder = {
+        Builder::new("tests/fixtures/sample-crate")
+            .unwrap()
+            .set_out_dir(&out_dir)
+            .disable_colors()
+    };
+
+    match builder.build_for_target_cpus(["sm_35", "sm_61"]).unwrap() {
+        MultiBuildStatus::Success(output) => {
+            let paths = output.get_assembly_paths();
+
+            for target_cpu in &["sm_35", "sm_61"] {
+                let cache_key = builder.get_cache_key(Some(target_cpu));
+
+                assert_eq!(
+                    paths[*target_cpu],
+                    out_dir.join(format!("sample_ptx_crate-{}-{}.ptx", target_cpu, cache_key))
+                );
+            }
+
+            for path in paths.values() {
+                let mut assembly_contents = String::new();
+
+                File::open(path)
+                    .unwrap()
+                    .read_to_string(&mut assembly_contents)
+                    .unwrap();
+
+                assert!(assembly_contents.contains(".visible .entry the_kernel("));
+            }
+
+            assert!(out_dir
+                .join("ptx-builder")
+                .join("sample_ptx_crate")
+                .is_dir());
+            assert!(!output.dependencies().unwrap().is_empty());
+        }
+
+        MultiBuildStatus::NotNeeded => unreachable!(),
+    }
+
+    remove_dir_all(&out_dir).unwrap_or_default();
+}
+
+#[test]
+fn should_build_in_custom_cache_root() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    let cache_root = env::temp_dir().join("ptx-builder-cache-root");
+    remove_dir_all(&cache_root).unwrap_or_default();
+
+    let builder = {
+        Builder::new("tests/fixtures/sample-crate")
+            .unwrap()
+            .set_cache_root(&cache_root)
+            .disable_colors()
+    };
+
+    match builder.build().unwrap() {
+        BuildStatus::Success(output) => {
+            assert!(output
+                .get_assembly_path()
+                .starts_with(cache_root.join("sample_ptx_crate")));
+
+            let entries = builder.get_cache().entries().unwrap();
+
+            assert_eq!(entries.len(), 1);
+            assert!(entries[0].get_path().join(".last-used").is_file());
+        }
+
+        BuildStatus::NotNeeded => unreachable!(),
+    }
+
+    remove_dir_all(&cache_root).unwrap_or_default();
+}
+
+#[test]
+fn should_provide_stable_cache_keys() {
+    let builder = Builder::new("tests/fixtures/sample-crate").unwrap();
+    let other_builder = Builder::new("tests/fixtures/sample-crate").unwrap();
+
+    assert_eq!(
+        builder.get_cache_key(Some("sm_61")),
+        other_builder.get_cache_key(Some("sm_61"))
+    );
+
+    assert_ne!(
+        builder.get_cache_key(Some("sm_61")),
+        builder.get_cache_key(Some("sm_35"))
+    );
+
+    assert_ne!(
+        builder.get_cache_key(None),
+        other_builder.add_feature("extra").get_cache_key(None)
+    );
+
+    let key = builder.get_cache_key(None);
+
+    assert_eq!(key.to_string().len(), 16);
+    assert!(format!("{:#}", key).contains("profile = \"release\""));
+}
+
 #[test]
 fn should_handle_rebuild_without_changes() {
     cleanup_temp_location();
@@ -171,12 +492,17 @@ fn should_handle_rebuild_without_changes() {
             .disable_colors()
     };
 
-    builder.build().unwrap();
+    match builder.build().unwrap() {
+        BuildStatus::Success(output) => assert!(output.was_rebuilt()),
+        BuildStatus::NotNeeded => unreachable!(),
+    }
 
     match builder.build().unwrap() {
         BuildStatus::Success(output) => {
             let mut assembly_contents = String::new();
 
+            assert!(!output.was_rebuilt());
+
             File::open(output.get_assembly_path())
                 .unwrap()
                 .read_to_string(&mut assembly_contents)
@@ -227,6 +553,255 @@ fn should_write_assembly_in_debug_mode() {
     }
 }
 
+#[test]
+fn should_write_assembly_for_target_cpu() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    let builder = Builder::new("tests/fixtures/sample-crate").unwrap();
+
+    match builder
+        .set_target_cpu("sm_61")
+        .disable_colors()
+        .build()
+        .unwrap()
+    {
+        BuildStatus::Success(output) => {
+            let module = output.get_module().unwrap();
+
+            assert_eq!(output.get_target_cpu(), Some("sm_61"));
+
+            assert_eq!(module.target, vec![String::from("sm_61")]);
+            assert!(module.get_kernel("the_kernel").is_some());
+        }
+
+        BuildStatus::NotNeeded => unreachable!(),
+    }
+}
+
+#[test]
+fn should_write_assembly_for_multiple_target_cpus() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    let builder = Builder::new("tests/fixtures/sample-crate")
+        .unwrap()
+        .disable_colors();
+
+    match builder.build_for_target_cpus(["sm_35", "sm_61"]).unwrap() {
+        MultiBuildStatus::Success(output) => {
+            let paths = output.get_assembly_paths();
+
+            assert_eq!(paths.len(), 2);
+            assert_ne!(paths["sm_35"], paths["sm_61"]);
+
+            for (target_cpu, output) in output.iter() {
+                let module = output.get_module().unwrap();
+
+                assert_eq!(output.get_target_cpu(), Some(target_cpu));
+
+                assert_eq!(module.target, vec![String::from(target_cpu)]);
+                assert!(module.get_kernel("the_kernel").is_some());
+            }
+        }
+
+        MultiBuildStatus::NotNeeded => unreachable!(),
+    }
+}
+
+#[test]
+fn should_write_assembly_with_target_features() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    let builder = Builder::new("tests/fixtures/sample-crate").unwrap();
+
+    match builder
+        .set_target_cpu("sm_70")
+        .add_target_feature("+ptx60")
+        .add_rustc_flag("-Copt-level=2")
+        .disable_colors()
+        .build()
+        .unwrap()
+    {
+        BuildStatus::Success(output) => {
+            let mut assembly_contents = String::new();
+
+            File::open(output.get_assembly_path())
+                .unwrap()
+                .read_to_string(&mut assembly_contents)
+                .unwrap();
+
+            assert!(assembly_contents.contains(".version 6.0"));
+            assert!(assembly_contents.contains(".target sm_70"));
+            assert!(assembly_contents.contains(".visible .entry the_kernel("));
+        }
+
+        BuildStatus::NotNeeded => unreachable!(),
+    }
+}
+
+#[test]
+fn should_build_with_selected_features() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    let builder = Builder::new("tests/fixtures/features-crate").unwrap();
+
+    match builder
+        .disable_default_features()
+        .add_feature("extra-kernel")
+        .disable_colors()
+        .build()
+        .unwrap()
+    {
+        BuildStatus::Success(output) => {
+            let mut assembly_contents = String::new();
+
+            File::open(output.get_assembly_path())
+                .unwrap()
+                .read_to_string(&mut assembly_contents)
+                .unwrap();
+
+            assert!(assembly_contents.contains(".visible .entry the_kernel("));
+            assert!(assembly_contents.contains(".visible .entry the_extra_kernel("));
+            assert!(assembly_contents.contains(".param .f64 the_kernel_param_2"));
+        }
+
+        BuildStatus::NotNeeded => unreachable!(),
+    }
+}
+
+#[test]
+fn should_build_with_default_features() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    let builder = Builder::new("tests/fixtures/features-crate").unwrap();
+
+    match builder.disable_colors().build().unwrap() {
+        BuildStatus::Success(output) => {
+            let mut assembly_contents = String::new();
+
+            File::open(output.get_assembly_path())
+                .unwrap()
+                .read_to_string(&mut assembly_contents)
+                .unwrap();
+
+            assert!(assembly_contents.contains(".visible .entry the_kernel("));
+            assert!(!assembly_contents.contains(".visible .entry the_extra_kernel("));
+            assert!(assembly_contents.contains(".param .f32 the_kernel_param_2"));
+        }
+
+        BuildStatus::NotNeeded => unreachable!(),
+    }
+}
+
+#[test]
+fn should_write_assembly_with_host_profile() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    env::set_var("PROFILE", "debug");
+    env::set_var("OPT_LEVEL", "1");
+    env::set_var("DEBUG", "false");
+
+    let builder = {
+        Builder::new("tests/fixtures/sample-crate")
+            .unwrap()
+            .set_profile_from_env()
+            .disable_colors()
+    };
+
+    let result = builder.build();
+
+    env::remove_var("PROFILE");
+    env::remove_var("OPT_LEVEL");
+    env::remove_var("DEBUG");
+
+    match result.unwrap() {
+        BuildStatus::Success(output) => {
+            let mut assembly_contents = String::new();
+
+            File::open(output.get_assembly_path())
+                .unwrap()
+                .read_to_string(&mut assembly_contents)
+                .unwrap();
+
+            assert!(output
+                .get_assembly_path()
+                .to_string_lossy()
+                .contains("debug"));
+
+            assert!(assembly_contents.contains(".visible .entry the_kernel("));
+        }
+
+        BuildStatus::NotNeeded => unreachable!(),
+    }
+}
+
+#[test]
+fn should_write_assembly_in_custom_profile() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    let builder = Builder::new("tests/fixtures/sample-crate").unwrap();
+
+    match builder
+        .set_profile(Profile::Custom("gpu-release".into()))
+        .disable_colors()
+        .build()
+        .unwrap()
+    {
+        BuildStatus::Success(output) => {
+            let mut assembly_contents = String::new();
+
+            File::open(output.get_assembly_path())
+                .unwrap()
+                .read_to_string(&mut assembly_contents)
+                .unwrap();
+
+            assert!(output
+                .get_assembly_path()
+                .to_string_lossy()
+                .contains("gpu-release"));
+
+            assert!(assembly_contents.contains(".visible .entry the_kernel("));
+            assert!(!output.dependencies().unwrap().is_empty());
+        }
+
+        BuildStatus::NotNeeded => unreachable!(),
+    }
+}
+
+#[test]
+fn should_build_with_specified_toolchain() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    let builder = Builder::new("tests/fixtures/sample-crate").unwrap();
+
+    match builder
+        .set_toolchain("nightly")
+        .disable_colors()
+        .build()
+        .unwrap()
+    {
+        BuildStatus::Success(output) => {
+            let mut assembly_contents = String::new();
+
+            File::open(output.get_assembly_path())
+                .unwrap()
+                .read_to_string(&mut assembly_contents)
+                .unwrap();
+
+            assert!(assembly_contents.contains(".visible .entry the_kernel("));
+        }
+
+        BuildStatus::NotNeeded => unreachable!(),
+    }
+}
+
 #[test]
 fn should_report_about_build_failure() {
     cleanup_temp_location();
@@ -237,52 +812,166 @@ fn should_report_about_build_failure() {
         .disable_colors();
 
     let output = builder.build();
-    let crate_absoulte_path = current_dir()
-        .unwrap()
-        .join("tests")
-        .join("fixtures")
-        .join("faulty-crate");
-
     let lib_path = PathBuf::from("src").join("lib.rs");
 
-    let crate_absoulte_path_str = crate_absoulte_path.display().to_string();
-
     match output.unwrap_err().kind() {
-        BuildErrorKind::BuildFailed(diagnostics) => {
-            assert_eq!(
+        BuildErrorKind::CompilationFailed(diagnostics) => {
+            let errors = {
                 diagnostics
                     .into_iter()
-                    .filter(|item| !item.contains("Blocking waiting")
-                        && !item.contains("Compiling core")
-                        && !item.contains("Compiling compiler_builtins")
-                        && !item.contains("Finished release [optimized] target(s)"))
-                    .collect::<Vec<_>>(),
-                &[
-                    format!(
-                        "   Compiling faulty-ptx_crate v0.1.0 ({})",
-                        crate_absoulte_path_str
-                    ),
-                    String::from("error[E0425]: cannot find function `external_fn` in this scope"),
-                    format!(" --> {}:6:20", lib_path.display()),
-                    String::from("  |"),
-                    String::from("6 |     *y.offset(0) = external_fn(*x.offset(0)) * a;"),
-                    String::from("  |                    ^^^^^^^^^^^ not found in this scope"),
-                    String::from(""),
-                    String::from("error: aborting due to previous error"),
-                    String::from(""),
-                    String::from(
-                        "For more information about this error, try `rustc --explain E0425`.",
-                    ),
-                    String::from("error: could not compile `faulty-ptx_crate`."),
-                    String::from(""),
-                ]
+                    .filter(|item| item.code.is_some())
+                    .collect::<Vec<_>>()
+            };
+
+            assert_eq!(errors.len(), 1);
+
+            assert_eq!(errors[0].level, DiagnosticLevel::Error);
+            assert_eq!(errors[0].code, Some(String::from("E0425")));
+            assert_eq!(
+                errors[0].message,
+                "cannot find function `external_fn` in this scope"
             );
+
+            assert_eq!(errors[0].file, Some(lib_path));
+            assert_eq!(errors[0].line, Some(6));
+            assert_eq!(errors[0].column, Some(20));
+
+            assert!(errors[0]
+                .rendered
+                .as_ref()
+                .unwrap()
+                .contains("6 |     *y.offset(0) = external_fn(*x.offset(0)) * a;"));
         }
 
         _ => unreachable!("it should fail with proper error"),
     }
 }
 
+#[test]
+fn should_report_build_outputs_to_cargo() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    let crate_path = {
+        current_dir()
+            .unwrap()
+            .join("tests")
+            .join("fixtures")
+            .join("sample-crate")
+    };
+
+    let out_dir = env::temp_dir().join("ptx-builder-cargo-out-dir");
+    remove_dir_all(&out_dir).unwrap_or_default();
+    create_dir_all(&out_dir).unwrap();
+    env::set_var("OUT_DIR", &out_dir);
+
+    let builder = Builder::new(crate_path.display().to_string()).unwrap();
+    let report = {
+        CargoAdapter::with_env_var("PTX_PATH")
+            .enable_bindings()
+            .enable_nul_terminated_assembly()
+            .enable_kernel_manifest()
+            .try_build(&builder.disable_colors())
+            .unwrap()
+    };
+
+    env::remove_var("OUT_DIR");
+
+    let env_vars: HashMap<_, _> = report.get_env_vars().iter().cloned().collect();
+    let mut env_names: Vec<_> = env_vars.keys().map(String::as_str).collect();
+
+    env_names.sort();
+    assert_eq!(
+        env_names,
+        &[
+            "PTX_PATH",
+            "PTX_PATH_BINDINGS",
+            "PTX_PATH_CSTR",
+            "PTX_PATH_KERNELS",
+        ]
+    );
+
+    let assembly = read_to_string(&env_vars["PTX_PATH"]).unwrap();
+    let nul_terminated_assembly = read_to_string(&env_vars["PTX_PATH_CSTR"]).unwrap();
+    let kernel_manifest: serde_json::Value =
+        serde_json::from_str(&read_to_string(&env_vars["PTX_PATH_KERNELS"]).unwrap()).unwrap();
+    let bindings = read_to_string(&env_vars["PTX_PATH_BINDINGS"]).unwrap();
+
+    assert!(assembly.contains(".visible .entry the_kernel("));
+    assert_eq!(nul_terminated_assembly, format!("{}\0", assembly));
+    assert_eq!(kernel_manifest["kernels"][0]["name"], "the_kernel");
+    assert!(bindings.contains("pub const THE_KERNEL: super::Kernel"));
+
+    assert_eq!(
+        PathBuf::from(&env_vars["PTX_PATH_BINDINGS"]),
+        out_dir.join("ptx_path.rs")
+    );
+
+    let mut outputs: Vec<_> = report.get_outputs().iter().map(PathBuf::as_path).collect();
+    let mut expected_outputs: Vec<_> = env_vars.values().map(Path::new).collect();
+
+    outputs.sort();
+    expected_outputs.sort();
+    assert_eq!(outputs, expected_outputs);
+
+    let cargo_lines = report.get_cargo_lines();
+
+    for source in &["lib.rs", "mod1.rs", "mod2.rs"] {
+        let line = format!(
+            "cargo:rerun-if-changed={}",
+            crate_path.join("src").join(source).display()
+        );
+
+        assert!(cargo_lines.contains(&line));
+    }
+
+    remove_dir_all(&out_dir).unwrap_or_default();
+}
+
+#[test]
+fn should_aggregate_build_set_failures() {
+    cleanup_temp_location();
+
+    let _lock = ENV_MUTEX.lock();
+    let vanished_crate_path = env::temp_dir().join("ptx-builder-vanished-crate");
+
+    for &parallel in &[false, true] {
+        create_dir_all(vanished_crate_path.join("src")).unwrap();
+
+        File::create(vanished_crate_path.join("Cargo.toml"))
+            .unwrap()
+            .write_all(b"[package]\nname = \"vanished-crate\"\nversion = \"0.1.0\"\n")
+            .unwrap();
+
+        File::create(vanished_crate_path.join("src").join("lib.rs")).unwrap();
+
+        let vanished_builder = Builder::new(vanished_crate_path.display().to_string()).unwrap();
+        remove_dir_all(&vanished_crate_path).unwrap();
+
+        let mut set = {
+            BuildSet::new()
+                .add(
+                    "SAMPLE_PTX_PATH",
+                    Builder::new("tests/fixtures/sample-crate").unwrap(),
+                )
+                .add("VANISHED_PTX_PATH", vanished_builder.disable_colors())
+        };
+
+        if parallel {
+            set = set.enable_parallel_builds();
+        }
+
+        let errors = set.try_build().unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+
+        match errors[0].kind() {
+            BuildErrorKind::CrateBuildFailed(path) => assert_eq!(path, vanished_crate_path),
+            _ => unreachable!("it should fail with proper error"),
+        }
+    }
+}
+
 #[test]
 fn should_provide_crate_source_files() {
     let _lock = ENV_MUTEX.lock();
@@ -295,7 +984,7 @@ fn should_provide_crate_source_files() {
             .join("sample-crate")
     };
 
-    let builder = Builder::new(&crate_path.display().to_string()).unwrap();
+    let builder = Builder::new(crate_path.display().to_string()).unwrap();
 
     match builder.disable_colors().build().unwrap() {
         BuildStatus::Success(output) => {
@@ -330,7 +1019,7 @@ fn should_provide_application_crate_source_files() {
             .join("app-crate")
     };
 
-    let builder = Builder::new(&crate_path.display().to_string()).unwrap();
+    let builder = Builder::new(crate_path.display().to_string()).unwrap();
 
     match builder.disable_colors().build().unwrap() {
         BuildStatus::Success(output) => {
@@ -358,7 +1047,7 @@ fn should_not_get_built_from_rls() {
     let _lock = ENV_MUTEX.lock();
     env::set_var("CARGO", "some/path/to/rls");
 
-    assert_eq!(Builder::is_build_needed(), false);
+    assert!(!Builder::is_build_needed());
     let builder = Builder::new("tests/fixtures/sample-crate").unwrap();
 
     match builder.disable_colors().build().unwrap() {
@@ -374,7 +1063,7 @@ fn should_not_get_built_recursively() {
     let _lock = ENV_MUTEX.lock();
     env::set_var("PTX_CRATE_BUILDING", "1");
 
-    assert_eq!(Builder::is_build_needed(), false);
+    assert!(!Builder::is_build_needed());
     let builder = Builder::new("tests/fixtures/sample-crate").unwrap();
 
     match builder.disable_colors().build().unwrap() {
@@ -391,6 +1080,9 @@ fn cleanup_temp_location() {
         "sample_app_ptx_crate",
         "sample_ptx_crate",
         "mixed_crate",
+        "features_crate",
+        "custom_layout_crate",
+        "workspace_kernels",
     ];
 
     for name in crate_names {
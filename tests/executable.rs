@@ -1,7 +1,7 @@
 use semver::VersionReq;
 
 use ptx_builder::error::*;
-use ptx_builder::executable::{Cargo, Executable, ExecutableRunner};
+use ptx_builder::executable::{Cargo, CommitDate, Executable, ExecutableRunner, ToolchainCargo};
 
 mod cargo {
     use super::*;
@@ -9,18 +9,31 @@ mod cargo {
     #[test]
     fn should_provide_output() {
         let output = ExecutableRunner::new(Cargo)
-            .with_args(&["rustc", "-q", "--", "--print", "crate-name"])
+            .with_args(["rustc", "-q", "--", "--print", "crate-name"])
             .with_cwd("tests/fixtures/sample-crate")
             .run();
 
-        assert_eq!(output.is_ok(), true);
+        assert!(output.is_ok());
         assert_eq!(output.unwrap().stdout, String::from("sample_ptx_crate\n"));
     }
 
+    #[test]
+    fn should_provide_toolchain_info() {
+        let toolchain = Cargo.get_current_toolchain().unwrap();
+
+        assert!(toolchain.commit_hash.is_some());
+        assert!(toolchain.commit_date.is_some());
+
+        assert_eq!(
+            toolchain.version.major,
+            Cargo.get_current_version().unwrap().major
+        );
+    }
+
     #[test]
     fn should_check_exit_code() {
         let output = ExecutableRunner::new(Cargo)
-            .with_args(&["rustc", "-q", "--unknown-flag"])
+            .with_args(["rustc", "-q", "--unknown-flag"])
             .with_cwd("tests/fixtures/sample-crate")
             .run();
 
@@ -41,6 +54,64 @@ mod cargo {
     }
 }
 
+mod toolchain_cargo {
+    use super::*;
+
+    #[test]
+    fn should_provide_output() {
+        let output = ExecutableRunner::new(ToolchainCargo::new("stable"))
+            .with_args(["rustc", "-q", "--", "--print", "crate-name"])
+            .with_cwd("tests/fixtures/sample-crate")
+            .run();
+
+        assert!(output.is_ok());
+        assert_eq!(output.unwrap().stdout, String::from("sample_ptx_crate\n"));
+    }
+
+    #[test]
+    fn should_check_toolchain_existence() {
+        let output = ExecutableRunner::new(ToolchainCargo::new("almost-unique-toolchain")).run();
+
+        match output.unwrap_err().kind() {
+            BuildErrorKind::CommandFailed {
+                command, stderr, ..
+            } => {
+                assert_eq!(command, String::from("cargo"));
+                assert!(stderr.contains("almost-unique-toolchain"));
+            }
+
+            _ => unreachable!("it should fail with proper error"),
+        }
+    }
+}
+
+mod unrealistic_commit_date_requirement {
+    use super::*;
+
+    #[test]
+    fn should_not_provide_output() {
+        let cargo =
+            { ToolchainCargo::current().with_required_commit_date(CommitDate::new(9999, 1, 1)) };
+
+        let output = ExecutableRunner::new(cargo).with_args(["-V"]).run();
+
+        match output.unwrap_err().kind() {
+            BuildErrorKind::CommandCommitDateNotFulfilled {
+                command,
+                required,
+                hint,
+                ..
+            } => {
+                assert_eq!(command, String::from("cargo"));
+                assert_eq!(required, CommitDate::new(9999, 1, 1));
+                assert!(hint.contains("rustup"));
+            }
+
+            _ => unreachable!("it should fail with proper error"),
+        }
+    }
+}
+
 mod non_existing_command {
     use super::*;
 
@@ -0,0 +1,17 @@
+#![feature(abi_ptx)]
+#![no_std]
+
+#[cfg(not(target_os = "cuda"))]
+fn main() {
+    println!("Hello, world!");
+}
+
+#[no_mangle]
+pub unsafe extern "ptx-kernel" fn the_reduction_kernel(x: *const f64, y: *mut f64, a: f64) {
+    *y.offset(0) = *x.offset(0) * a;
+}
+
+#[panic_handler]
+fn dummy_panic_handler(_info: &::core::panic::PanicInfo) -> ! {
+    loop {}
+}
@@ -0,0 +1,25 @@
+#![feature(abi_ptx, core_intrinsics)]
+#![no_std]
+
+#[cfg(feature = "single-precision")]
+type Float = f32;
+
+#[cfg(not(feature = "single-precision"))]
+type Float = f64;
+
+#[no_mangle]
+pub unsafe extern "ptx-kernel" fn the_kernel(x: *const Float, y: *mut Float, a: Float) {
+    *y.offset(0) = *x.offset(0) * a;
+}
+
+#[cfg(feature = "extra-kernel")]
+#[no_mangle]
+pub unsafe extern "ptx-kernel" fn the_extra_kernel(x: *const Float, y: *mut Float) {
+    *y.offset(0) = *x.offset(0);
+}
+
+#[panic_handler]
+unsafe fn breakpoint_panic_handler(_: &::core::panic::PanicInfo) -> ! {
+    core::intrinsics::breakpoint();
+    core::hint::unreachable_unchecked();
+}
@@ -1,8 +1,19 @@
+use std::env;
+use std::path::PathBuf;
+
+use antidote::Mutex;
 use failure::ResultExt;
+use lazy_static::*;
 
 use ptx_builder::error::*;
+use ptx_builder::messages::{Diagnostic, DiagnosticLevel};
+use ptx_builder::prelude::*;
 use ptx_builder::reporter::ErrorLogPrinter;
 
+lazy_static! {
+    static ref ENV_MUTEX: Mutex<()> = Mutex::new(());
+}
+
 #[test]
 fn should_report_in_cargo_style() {
     let original_error: Result<()> = Err(Error::from(BuildErrorKind::CommandFailed {
@@ -45,3 +56,162 @@ fn should_report_in_cargo_style() {
 [PTX]   output"
     );
 }
+
+#[test]
+fn should_report_compiler_diagnostics() {
+    let error = Error::from(BuildErrorKind::CompilationFailed(vec![
+        Diagnostic {
+            level: DiagnosticLevel::Error,
+            code: Some(String::from("E0425")),
+            message: String::from("cannot find function `external_fn` in this scope"),
+            file: Some(PathBuf::from("src/lib.rs")),
+            line: Some(6),
+            column: Some(20),
+            rendered: None,
+        },
+        Diagnostic {
+            level: DiagnosticLevel::Error,
+            code: None,
+            message: String::from("aborting due to previous error"),
+            file: None,
+            line: None,
+            column: None,
+            rendered: Some(String::from("error: aborting due to previous error\n\n")),
+        },
+    ]));
+
+    let mut reporter = ErrorLogPrinter::print(error);
+
+    assert_eq!(
+        reporter.disable_colors().to_string(),
+        "[PTX] Unable to build a PTX crate!
+[PTX] error[E0425]: cannot find function `external_fn` in this scope
+[PTX]  --> src/lib.rs:6:20
+[PTX] error: aborting due to previous error"
+    );
+}
+
+#[test]
+fn should_report_failed_device_crate() {
+    let original_error: Result<()> = Err(Error::from(BuildErrorKind::InternalError(String::from(
+        "internal error",
+    ))));
+
+    let chained_error = original_error
+        .with_context(|_| BuildErrorKind::CrateBuildFailed(PathBuf::from("/path/to/math-kernels")));
+
+    let mut reporter = ErrorLogPrinter::print(chained_error.unwrap_err().into());
+
+    assert_eq!(
+        reporter.disable_colors().to_string(),
+        "[PTX] Unable to build device crate: '/path/to/math-kernels'
+[PTX]
+[PTX] caused by:
+[PTX]   Internal error: internal error"
+    );
+}
+
+#[test]
+fn should_return_report_when_build_is_not_needed() {
+    let _lock = ENV_MUTEX.lock();
+    env::set_var("PTX_CRATE_BUILDING", "1");
+
+    let builder = Builder::new("tests/fixtures/sample-crate").unwrap();
+    let adapter = {
+        CargoAdapter::with_env_var("PTX_PATH")
+            .enable_bindings()
+            .enable_nul_terminated_assembly()
+            .enable_kernel_manifest()
+    };
+
+    let report = adapter.try_build(&builder).unwrap();
+    let multi_report = {
+        adapter
+            .try_build_for_target_cpus(&builder, ["sm_35", "sm_61"])
+            .unwrap()
+    };
+
+    env::set_var("PTX_CRATE_BUILDING", "");
+
+    assert_eq!(
+        report.get_cargo_lines(),
+        vec![
+            "cargo:rustc-env=PTX_PATH=/dev/null",
+            "cargo:rustc-env=PTX_PATH_KERNELS=/dev/null",
+            "cargo:rustc-env=PTX_PATH_CSTR=/dev/null",
+            "cargo:rustc-env=PTX_PATH_BINDINGS=/dev/null",
+        ]
+    );
+
+    assert_eq!(
+        multi_report.get_cargo_lines(),
+        vec![
+            "cargo:rustc-env=PTX_PATH_SM_35=/dev/null",
+            "cargo:rustc-env=PTX_PATH_SM_35_KERNELS=/dev/null",
+            "cargo:rustc-env=PTX_PATH_SM_35_CSTR=/dev/null",
+            "cargo:rustc-env=PTX_PATH_SM_35_BINDINGS=/dev/null",
+            "cargo:rustc-env=PTX_PATH_SM_61=/dev/null",
+            "cargo:rustc-env=PTX_PATH_SM_61_KERNELS=/dev/null",
+            "cargo:rustc-env=PTX_PATH_SM_61_CSTR=/dev/null",
+            "cargo:rustc-env=PTX_PATH_SM_61_BINDINGS=/dev/null",
+            "cargo:rustc-env=PTX_PATH_MANIFEST=/dev/null",
+        ]
+    );
+
+    for report in &[report, multi_report] {
+        assert!(report.get_dependencies().is_empty());
+        assert!(report.get_outputs().is_empty());
+        assert!(report.get_warnings().is_empty());
+    }
+}
+
+#[test]
+fn should_report_kernels_mismatch() {
+    let error = Error::from(BuildErrorKind::KernelsMismatch {
+        missing: vec![String::from("add_vectors"), String::from("scale_vector")],
+        unexpected: vec![String::from("add_vector")],
+    });
+
+    let mut reporter = ErrorLogPrinter::print(error);
+
+    assert_eq!(
+        reporter.disable_colors().to_string(),
+        "[PTX] Unexpected kernels in the assembly!
+[PTX] missing: 'add_vectors', 'scale_vector'
+[PTX] unexpected: 'add_vector'"
+    );
+}
+
+#[test]
+fn should_aggregate_build_set_reports_when_build_is_not_needed() {
+    let _lock = ENV_MUTEX.lock();
+    env::set_var("PTX_CRATE_BUILDING", "1");
+
+    for &parallel in &[false, true] {
+        let mut set = {
+            BuildSet::new()
+                .add(
+                    "SAMPLE_PTX_PATH",
+                    Builder::new("tests/fixtures/sample-crate").unwrap(),
+                )
+                .add_with_adapter(
+                    CargoAdapter::with_env_var("APP_PTX_PATH").enable_nul_terminated_assembly(),
+                    Builder::new("tests/fixtures/app-crate").unwrap(),
+                )
+        };
+
+        if parallel {
+            set = set.enable_parallel_builds();
+        }
+
+        let report = set.try_build().unwrap();
+        let env_names: Vec<_> = report.get_env_vars().iter().map(|(name, _)| name).collect();
+
+        assert_eq!(
+            env_names,
+            &["SAMPLE_PTX_PATH", "APP_PTX_PATH", "APP_PTX_PATH_CSTR",]
+        );
+    }
+
+    env::set_var("PTX_CRATE_BUILDING", "");
+}